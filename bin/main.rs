@@ -5,23 +5,113 @@ use std::io::Write;
 #[command(long_about = None)]
 struct Args {
     /// Duration of the simulation experiment, in s
-    #[arg(long, default_value_t = 3600)]
-    duration: u64,
+    #[arg(long, default_value_t = 3600.0)]
+    duration: f64,
     /// Average lifetime duration of a job, in s
     #[arg(long, default_value_t = 10.0)]
     job_lifetime: f64,
+    /// How the tasks of a job are executed, one of: pipeline (all tasks
+    /// live as long as the job), batch (each task has its own duration from
+    /// data/task_duration_dist.dat and starts after its predecessors)
+    #[arg(long, default_value_t = String::from("pipeline"))]
+    job_execution: String,
     /// Average inter-arrival between consecutive jobs, in s
     #[arg(long, default_value_t = 1.0)]
     job_interarrival: f64,
+    /// Profile over time of the job arrival rate, one of: constant,
+    /// diurnal, piecewise
+    #[arg(long, default_value_t = String::from("constant"))]
+    arrival_profile: String,
+    /// Relative amplitude of the diurnal arrival rate profile
+    #[arg(long, default_value_t = 0.5)]
+    diurnal_amplitude: f64,
+    /// Period of the diurnal arrival rate profile, in s
+    #[arg(long, default_value_t = 86400.0)]
+    diurnal_period: f64,
+    /// File with the piecewise arrival rate profile, one change per line as
+    /// start-time,rate-multiplier where the multiplier is relative to the
+    /// inverse of --job-interarrival
+    #[arg(long, default_value_t = String::from("data/arrival_rates.csv"))]
+    arrival_rates: String,
+    /// Distribution of the interval between consecutive jobs, one of:
+    /// exponential, empirical (from data/job_interval_dist.dat),
+    /// empirical-rescaled (same, with mean --job-interarrival)
+    #[arg(long, default_value_t = String::from("exponential"))]
+    interarrival_distribution: String,
+    /// Alibaba 2018 batch_task.csv file whose jobs are replayed at their
+    /// arrival times, with their own lifetimes and task durations, instead
+    /// of generating synthetic jobs
+    #[arg(long)]
+    alibaba_trace: Option<String>,
+    /// Replay every task of --alibaba-trace with the resources of one of its
+    /// instances, instead of those of all its instances together
+    #[arg(long, default_value_t = false)]
+    alibaba_single_instance: bool,
+    /// File with the catalog of jobs from which those arriving are drawn,
+    /// one per line as job-file,mix-probability where the job file is in
+    /// JSON or Graphviz DOT format, instead of generating synthetic jobs
+    #[arg(long)]
+    job_catalog: Option<String>,
     /// Invocation rate of a job in its lifetime, in Hz
     #[arg(long, default_value_t = 5.0)]
     job_invocation_rate: f64,
     /// Node capacity, every 100 unit means 1 core
     #[arg(long, default_value_t = 1000)]
     node_capacity: usize,
-    /// Defragmentation interval, ins
-    #[arg(long, default_value_t = 300)]
-    defragmentation_interval: u64,
+    /// Node memory capacity, in MB
+    #[arg(long, default_value_t = 1048576)]
+    node_memory: usize,
+    /// Power consumed by an idle node, in W
+    #[arg(long, default_value_t = 50.0)]
+    node_idle_power: f64,
+    /// Power consumed by a node with full CPU load, in W
+    #[arg(long, default_value_t = 100.0)]
+    node_peak_power: f64,
+    /// Price of using a node for one hour
+    #[arg(long, default_value_t = 1.0)]
+    node_price: f64,
+    /// File with the catalog of node types, one per line as
+    /// name,cpu,memory,idle-power,peak-power,hourly-price,
+    /// if specified then --node-capacity, --node-memory, --node-idle-power,
+    /// --node-peak-power, and --node-price are ignored; stateless policies
+    /// use only the type with the lowest price per CPU
+    #[arg(long, default_value_t = String::from(""))]
+    node_types: String,
+    /// Energy consumed to transfer one byte between two nodes, in uJ
+    #[arg(long, default_value_t = 0.4)]
+    network_energy: f64,
+    /// Defragmentation interval, in s
+    #[arg(long, default_value_t = 300.0)]
+    defragmentation_interval: f64,
+    /// Fragmentation index above which defragmentation occurs, with rounds
+    /// spaced by at least the defragmentation interval, if not specified
+    /// then defragmentation occurs periodically
+    #[arg(long)]
+    defragmentation_threshold: Option<f64>,
+    /// Defragmentation mode, one of: none, full, incremental, cost-benefit,
+    /// which applies to any stateful policy, if not specified then full
+    /// with stateful-best-fit and none with the other policies
+    #[arg(long)]
+    defragmentation_mode: Option<String>,
+    /// Order in which tasks are re-allocated in a full repack, one of:
+    /// arrival, task-cpu, job-cpu, job-state, remaining-lifetime
+    #[arg(long, default_value_t = String::from("arrival"))]
+    repacking_order: String,
+    /// Bandwidth to transfer the state of a migrated task, in MB/s, if not
+    /// specified then migrations are instantaneous
+    #[arg(long)]
+    migration_bandwidth: Option<f64>,
+    /// Maximum number of tasks migrated per incremental defragmentation round
+    #[arg(long)]
+    migration_budget_tasks: Option<usize>,
+    /// Maximum state migrated per incremental defragmentation round, in MB
+    #[arg(long)]
+    migration_budget_state: Option<usize>,
+    /// How the remaining lifetime of jobs is known in cost-benefit
+    /// defragmentation, one of: oracle, estimated (only with pipeline
+    /// execution of synthetic or catalog jobs, whose lifetimes are exponential)
+    #[arg(long, default_value_t = String::from("oracle"))]
+    remaining_lifetime: String,
     /// State size multiplier applied to the task memory size.
     #[arg(long, default_value_t = 100.0)]
     state_mul: f64,
@@ -34,6 +124,21 @@ struct Args {
     /// Final seed to initialize the pseudo-random number generators
     #[arg(long, default_value_t = 10)]
     seed_end: u64,
+    /// Comma-separated names of the metrics whose 95% confidence interval
+    /// is used to stop adding replications beyond --seed-end, if empty
+    /// then the number of replications is fixed
+    #[arg(long, default_value_t = String::from(""))]
+    ci_metrics: String,
+    /// Target half-width of the confidence intervals, relative to the mean
+    #[arg(long, default_value_t = 0.05)]
+    ci_target: f64,
+    /// Maximum number of replications with confidence-driven stopping
+    #[arg(long, default_value_t = 100)]
+    ci_max_replications: usize,
+    /// Name of the CSV output file where to save the mean and confidence
+    /// interval half-width of the metrics in --ci-metrics
+    #[arg(long, default_value_t = String::from("ci.csv"))]
+    ci_output: String,
     /// Number of parallel workers
     #[arg(long, default_value_t = std::thread::available_parallelism().unwrap().get())]
     concurrency: usize,
@@ -43,6 +148,23 @@ struct Args {
     /// Name of the CSV output file where to save the metrics collected.
     #[arg(long, default_value_t = String::from("out.csv"))]
     output: String,
+    /// Warm-up period excluded from the metrics, either a duration in s,
+    /// or none, or mser-5 to detect it from the sampled busy nodes
+    #[arg(long, default_value_t = String::from("none"))]
+    warm_up: String,
+    /// Interval at which the metrics are sampled, in s, if not specified
+    /// then the metrics are not sampled
+    #[arg(long)]
+    sampling_interval: Option<f64>,
+    /// Prefix of the CSV files where to save the sampled metrics,
+    /// one per seed named <prefix>-<seed>.csv
+    #[arg(long, default_value_t = String::from("samples"))]
+    sampling_output: String,
+    /// Prefix of the JSON Lines files where to save the trace of events,
+    /// one per seed named <prefix>-<seed>.jsonl, if not specified then
+    /// no trace is saved
+    #[arg(long)]
+    trace: Option<String>,
     /// Append to the output file.
     #[arg(long, default_value_t = false)]
     append: bool,
@@ -54,8 +176,7 @@ struct Args {
     additional_header: String,
 }
 
-#[tokio::main]
-async fn main() -> anyhow::Result<()> {
+fn main() -> anyhow::Result<()> {
     env_logger::init();
 
     let args = Args::parse();
@@ -65,82 +186,170 @@ async fn main() -> anyhow::Result<()> {
         "--additional_fields and --additional_header have a different number of commas"
     );
 
-    if args.policy == "list" {
-        println!(
-            "available policies: {}",
-            stateful_faas_sim::simulation::Policy::all()
-                .iter()
-                .map(|x| format!("{}", x))
-                .collect::<Vec<String>>()
-                .join(", ")
+    let ci_metrics = args
+        .ci_metrics
+        .split(',')
+        .filter(|x| !x.is_empty())
+        .collect::<Vec<&str>>();
+    for metric in &ci_metrics {
+        anyhow::ensure!(
+            stateful_faas_sim::simulation::Output::metrics().contains(metric),
+            "unknown metric: {}, available metrics: {}",
+            metric,
+            stateful_faas_sim::simulation::Output::metrics().join(", ")
         );
-        return Ok(());
-    }
-    let policy = stateful_faas_sim::simulation::Policy::from(&args.policy)?;
-
-    // create the configurations of all the experiments
-    let configurations = std::sync::Arc::new(std::sync::Mutex::new(vec![]));
-    for seed in args.seed_init..args.seed_end {
-        configurations
-            .lock()
-            .unwrap()
-            .push(stateful_faas_sim::simulation::Config {
-                duration: args.duration,
-                job_lifetime: args.job_lifetime,
-                job_interarrival: args.job_interarrival,
-                job_invocation_rate: args.job_invocation_rate,
-                node_capacity: args.node_capacity,
-                defragmentation_interval: args.defragmentation_interval,
-                policy: policy.clone(),
-                state_mul: args.state_mul,
-                arg_mul: args.arg_mul,
-                seed,
-            });
     }
+    anyhow::ensure!(args.ci_target > 0.0, "vanishing confidence interval target");
 
-    let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
-    for i in 0..args.concurrency {
-        let tx = tx.clone();
-        let configurations = configurations.clone();
-        tokio::spawn(async move {
-            log::info!("spawned worker #{}", i);
-            loop {
-                let config;
-                {
-                    if let Some(val) = configurations.lock().unwrap().pop() {
-                        config = Some(val);
-                    } else {
-                        break;
-                    }
-                }
-                match stateful_faas_sim::simulation::Simulation::new(config.unwrap()) {
-                    Ok(mut sim) => tx.send(sim.run()).unwrap(),
-                    Err(err) => log::error!("error when running simulation: {}", err),
-                };
-            }
-            log::info!("terminated worker #{}", i);
-        });
+    let registry = stateful_faas_sim::policy::PolicyRegistry::new();
+    if args.policy == "list" {
+        println!("available policies: {}", registry.names().join(", "));
+        return Ok(());
     }
-    let _ = || tx;
+    let policy = registry.get(&args.policy)?;
+
+    let node_types = match args.node_types.is_empty() {
+        true => vec![stateful_faas_sim::cluster::NodeType::new(
+            "default",
+            stateful_faas_sim::cluster::Resources::new(args.node_capacity, args.node_memory),
+            args.node_idle_power,
+            args.node_peak_power,
+            args.node_price,
+        )],
+        false => stateful_faas_sim::cluster::NodeType::from_file(&args.node_types)?,
+    };
+
+    let arrival_profile = match args.arrival_profile.as_str() {
+        "constant" => stateful_faas_sim::arrival::ArrivalProfile::Constant,
+        "diurnal" => stateful_faas_sim::arrival::ArrivalProfile::Diurnal {
+            amplitude: args.diurnal_amplitude,
+            period: args.diurnal_period,
+        },
+        "piecewise" => stateful_faas_sim::arrival::ArrivalProfile::from_file(&args.arrival_rates)?,
+        _ => anyhow::bail!("unknown arrival profile: {}", args.arrival_profile),
+    };
+
+    let job_execution = args
+        .job_execution
+        .parse::<stateful_faas_sim::job::JobExecution>()?;
+    let interarrival_distribution =
+        args.interarrival_distribution
+            .parse::<stateful_faas_sim::arrival::InterarrivalDistribution>()?;
+
+    let defragmentation_mode = match &args.defragmentation_mode {
+        Some(defragmentation_mode) => defragmentation_mode
+            .parse::<stateful_faas_sim::defragmentation::DefragmentationMode>(
+        )?,
+        None => match policy.name() {
+            "stateful-best-fit" => stateful_faas_sim::defragmentation::DefragmentationMode::Full,
+            _ => stateful_faas_sim::defragmentation::DefragmentationMode::None,
+        },
+    };
+    let repacking_order = args
+        .repacking_order
+        .parse::<stateful_faas_sim::defragmentation::RepackingOrder>()?;
+    let remaining_lifetime = args
+        .remaining_lifetime
+        .parse::<stateful_faas_sim::defragmentation::RemainingLifetime>()?;
+    let warm_up = args.warm_up.parse::<stateful_faas_sim::warm_up::WarmUp>()?;
+    let migration_budget = stateful_faas_sim::defragmentation::MigrationBudget {
+        tasks: args.migration_budget_tasks,
+        state: args.migration_budget_state,
+    };
+
+    // read the trace once, its jobs are replayed with every seed
+    let alibaba_trace = match &args.alibaba_trace {
+        Some(filename) => Some(std::sync::Arc::new(
+            stateful_faas_sim::alibaba::read_batch_tasks(
+                filename,
+                args.state_mul,
+                args.arg_mul,
+                args.alibaba_single_instance,
+                &node_types,
+            )?,
+        )),
+        None => None,
+    };
 
-    // wait until all the simulations have been done
-    let mut outputs = vec![];
-    while let Some(output) = rx.recv().await {
-        outputs.push(output);
+    // create the configuration of the experiment with a given seed
+    let make_config = |seed| stateful_faas_sim::simulation::Config {
+        duration: args.duration,
+        job_lifetime: args.job_lifetime,
+        job_execution,
+        job_interarrival: args.job_interarrival,
+        arrival_profile: arrival_profile.clone(),
+        interarrival_distribution,
+        job_invocation_rate: args.job_invocation_rate,
+        node_types: node_types.clone(),
+        network_energy: args.network_energy,
+        defragmentation_interval: args.defragmentation_interval,
+        defragmentation_threshold: args.defragmentation_threshold,
+        defragmentation_mode,
+        repacking_order,
+        migration_bandwidth: args.migration_bandwidth,
+        migration_budget,
+        remaining_lifetime,
+        policy: policy.clone(),
+        state_mul: args.state_mul,
+        arg_mul: args.arg_mul,
+        seed,
+        warm_up,
+        sampling_interval: args.sampling_interval,
+        trace: args
+            .trace
+            .as_ref()
+            .map(|prefix| format!("{}-{}.jsonl", prefix, seed)),
+        alibaba_trace: alibaba_trace.clone(),
+        job_catalog: args.job_catalog.clone(),
+    };
+
+    let mut outputs = stateful_faas_sim::simulation::run_batch(
+        (args.seed_init..args.seed_end).map(make_config).collect(),
+        args.concurrency,
+    )?;
+
+    // add replications, with new seeds, until the confidence intervals
+    // of all the metrics are narrow enough
+    let mut confidence_intervals = vec![];
+    if !ci_metrics.is_empty() {
+        let mut next_seed = args.seed_end;
+        loop {
+            confidence_intervals = ci_metrics
+                .iter()
+                .map(|metric| {
+                    stateful_faas_sim::confidence::ConfidenceInterval::new(
+                        &outputs
+                            .iter()
+                            .map(|x| x.metric(metric).unwrap())
+                            .collect::<Vec<f64>>(),
+                    )
+                })
+                .collect::<Vec<Option<stateful_faas_sim::confidence::ConfidenceInterval>>>();
+            let converged = confidence_intervals
+                .iter()
+                .all(|x| x.is_some_and(|x| x.relative_half_width() <= args.ci_target));
+            if converged || outputs.len() >= args.ci_max_replications {
+                break;
+            }
+            let num_replications =
+                usize::min(args.concurrency, args.ci_max_replications - outputs.len()) as u64;
+            log::info!(
+                "adding {} replications to the current {}",
+                num_replications,
+                outputs.len()
+            );
+            outputs.extend(stateful_faas_sim::simulation::run_batch(
+                (next_seed..next_seed + num_replications)
+                    .map(make_config)
+                    .collect(),
+                args.concurrency,
+            )?);
+            next_seed += num_replications;
+        }
     }
 
     // save output to file
-    let header = !args.append
-        || match std::fs::metadata(&args.output) {
-            Ok(metadata) => metadata.len() == 0,
-            Err(_) => true,
-        };
-    let mut f = std::fs::OpenOptions::new()
-        .write(true)
-        .append(args.append)
-        .create(true)
-        .truncate(!args.append)
-        .open(args.output)?;
+    let (mut f, header) = open_output(&args.output, args.append)?;
 
     if header {
         writeln!(
@@ -151,9 +360,73 @@ async fn main() -> anyhow::Result<()> {
         )?;
     }
 
-    for output in outputs {
+    for output in &outputs {
         writeln!(&mut f, "{}{}", args.additional_fields, output)?;
     }
 
+    // save the confidence intervals to file
+    if !ci_metrics.is_empty() {
+        let (mut f, header) = open_output(&args.ci_output, args.append)?;
+        if header {
+            writeln!(
+                &mut f,
+                "{}replications,{}",
+                args.additional_header,
+                ci_metrics
+                    .iter()
+                    .map(|x| format!("{}-mean,{}-ci", x, x))
+                    .collect::<Vec<String>>()
+                    .join(",")
+            )?;
+        }
+        writeln!(
+            &mut f,
+            "{}{},{}",
+            args.additional_fields,
+            outputs.len(),
+            confidence_intervals
+                .iter()
+                .map(|x| match x {
+                    Some(x) => format!("{},{}", x.mean, x.half_width),
+                    None => String::from(","),
+                })
+                .collect::<Vec<String>>()
+                .join(",")
+        )?;
+    }
+
+    // save the sampled metrics to one file per seed
+    if args.sampling_interval.is_some() {
+        for output in &outputs {
+            let mut f =
+                std::fs::File::create(format!("{}-{}.csv", args.sampling_output, output.seed))?;
+            writeln!(
+                &mut f,
+                "{}",
+                stateful_faas_sim::simulation::Sample::header()
+            )?;
+            for sample in &output.samples {
+                writeln!(&mut f, "{}", sample)?;
+            }
+        }
+    }
+
     Ok(())
 }
+
+/// Open an output file, in append mode or truncating it, and return
+/// whether the header should be written, i.e., if the file is empty.
+fn open_output(filename: &str, append: bool) -> anyhow::Result<(std::fs::File, bool)> {
+    let header = !append
+        || match std::fs::metadata(filename) {
+            Ok(metadata) => metadata.len() == 0,
+            Err(_) => true,
+        };
+    let f = std::fs::OpenOptions::new()
+        .write(true)
+        .append(append)
+        .create(true)
+        .truncate(!append)
+        .open(filename)?;
+    Ok((f, header))
+}
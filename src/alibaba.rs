@@ -0,0 +1,304 @@
+use crate::job::{Edge, Job, Vertex};
+
+/// Job read from a trace, to be replayed during a simulation.
+#[derive(Debug, Clone)]
+pub struct TraceJob {
+    /// Name of the job in the trace.
+    pub name: String,
+    /// Arrival time, relative to that of the first job in the trace, in s.
+    pub arrival: f64,
+    /// Time from the arrival of the job to the end of its last task, in s.
+    pub lifetime: f64,
+    pub job: Job,
+    /// Duration of each task, in s.
+    pub durations: Vec<f64>,
+}
+
+/// Task of a job in the trace.
+struct TraceTask {
+    name: String,
+    num_instances: usize,
+    start_time: f64,
+    end_time: f64,
+    plan_cpu: f64,
+    plan_mem: f64,
+}
+
+/// Parse the name of a task with the dependency encoding of the trace,
+/// e.g., `R4_1_2_3` is task 4 depending on tasks 1, 2, and 3.
+/// Return the ID of the task and those of the tasks on which it depends,
+/// or `None` if the name does not encode them, e.g., `task_Nzg3...` or
+/// `MergeTask`, as for independent tasks.
+fn parse_task_name(name: &str) -> Option<(u32, Vec<u32>)> {
+    let mut ids = name
+        .trim_start_matches(|x: char| x.is_ascii_alphabetic())
+        .split('_')
+        .map(|x| x.parse::<u32>());
+    let task_id = ids.next()?.ok()?;
+    let dependencies = ids.collect::<Result<Vec<u32>, _>>().ok()?;
+    Some((task_id, dependencies))
+}
+
+/// Read the jobs from a file in the format of `batch_task.csv` of the
+/// Alibaba cluster trace 2018, with one task per line: task name, number
+/// of instances, job name, task type, status, start time, end time (in s),
+/// planned CPU (every 100 unit means 1 core), planned memory (the fraction
+/// of 100 unit). Empty lines and those beginning with `#` are ignored, as
+/// are the tasks whose status is not `Terminated`, since their start and
+/// end times are not known.
+///
+/// Every task becomes a vertex with the resources planned for all its
+/// instances together, or for one of them if `single_instance` is true,
+/// where the state size is the planned memory multiplied by `state_mul`,
+/// and every dependency becomes an edge whose argument size is the planned
+/// memory of the task producing it multiplied by `arg_mul`.
+/// Missing planned resources are taken as zero. Dependencies on tasks
+/// not in the trace are ignored. The jobs with cyclic dependencies or that
+/// cannot be simulated with the given node types, see [`Job::validate`],
+/// are skipped.
+///
+/// A job arrives when its first task starts and ends with its last task.
+/// The jobs are returned in order of arrival.
+pub fn read_batch_tasks(
+    filename: &str,
+    state_mul: f64,
+    arg_mul: f64,
+    single_instance: bool,
+    node_types: &[crate::cluster::NodeType],
+) -> anyhow::Result<Vec<TraceJob>> {
+    let infile = std::fs::File::open(filename)
+        .map_err(|err| anyhow::anyhow!("could not read trace file {}: {}", filename, err))?;
+    let parse_or_zero = |x: &str| -> anyhow::Result<f64> {
+        let value = match x.is_empty() {
+            true => 0.0,
+            false => x.parse::<f64>()?,
+        };
+        anyhow::ensure!(
+            value.is_finite() && value >= 0.0,
+            "invalid resources: {}",
+            x
+        );
+        Ok(value)
+    };
+
+    // group the tasks by job, in order of appearance
+    let mut job_names = vec![];
+    let mut job_tasks: std::collections::HashMap<String, Vec<TraceTask>> =
+        std::collections::HashMap::new();
+    for (i, line) in std::io::BufRead::lines(std::io::BufReader::new(infile)).enumerate() {
+        let line = line?;
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let tokens = line.split(',').map(|x| x.trim()).collect::<Vec<&str>>();
+        anyhow::ensure!(tokens.len() == 9, format!("invalid line {}", i));
+        if tokens[4] != "Terminated" {
+            continue;
+        }
+        let num_instances = match single_instance {
+            true => 1,
+            false => tokens[1]
+                .parse::<usize>()
+                .map_err(|err| anyhow::anyhow!("invalid line {}: {}", i, err))?,
+        };
+        anyhow::ensure!(num_instances > 0, format!("invalid line {}", i));
+        let task = TraceTask {
+            name: tokens[0].to_string(),
+            num_instances,
+            start_time: tokens[5].parse::<f64>()?,
+            end_time: tokens[6].parse::<f64>()?,
+            plan_cpu: parse_or_zero(tokens[7])
+                .map_err(|err| anyhow::anyhow!("invalid line {}: {}", i, err))?,
+            plan_mem: parse_or_zero(tokens[8])
+                .map_err(|err| anyhow::anyhow!("invalid line {}: {}", i, err))?,
+        };
+        anyhow::ensure!(
+            task.start_time.is_finite() && task.end_time.is_finite(),
+            format!("invalid line {}", i)
+        );
+        job_tasks
+            .entry(tokens[2].to_string())
+            .or_insert_with(|| {
+                job_names.push(tokens[2].to_string());
+                vec![]
+            })
+            .push(task);
+    }
+
+    let mut jobs = vec![];
+    for name in job_names {
+        let tasks = &job_tasks[&name];
+        let mut task_ids = std::collections::HashMap::new();
+        for (index, task) in tasks.iter().enumerate() {
+            if let Some((task_id, _)) = parse_task_name(&task.name) {
+                anyhow::ensure!(
+                    task_ids.insert(task_id, index as u32).is_none(),
+                    "duplicate task {} in job {}",
+                    task_id,
+                    name
+                );
+            }
+        }
+        let vertices = tasks
+            .iter()
+            .map(|x| {
+                Vertex::new(
+                    x.plan_cpu as usize * x.num_instances,
+                    (x.plan_mem * x.num_instances as f64 * state_mul).round() as usize,
+                )
+            })
+            .collect::<Vec<Vertex>>();
+        let mut edges = vec![];
+        for (index, task) in tasks.iter().enumerate() {
+            let Some((_, dependencies)) = parse_task_name(&task.name) else {
+                continue;
+            };
+            for dependency in dependencies {
+                if let Some(u) = task_ids.get(&dependency) {
+                    let producer = &tasks[*u as usize];
+                    let arg_size = (producer.plan_mem * producer.num_instances as f64 * arg_mul)
+                        .round() as usize;
+                    edges.push((*u, index as u32, Edge::new(arg_size)));
+                }
+            }
+        }
+        let job = Job::new(vertices, edges);
+        if petgraph::algo::is_cyclic_directed(&job.graph) {
+            log::warn!("skipping job {} with cyclic dependencies", name);
+            continue;
+        }
+        if let Err(err) = job.validate(node_types) {
+            log::warn!("skipping job {}: {}", name, err);
+            continue;
+        }
+
+        let arrival = tasks.iter().map(|x| x.start_time).fold(f64::MAX, f64::min);
+        let end = tasks.iter().map(|x| x.end_time).fold(arrival, f64::max);
+        jobs.push(TraceJob {
+            name,
+            arrival,
+            lifetime: end - arrival,
+            job,
+            durations: tasks
+                .iter()
+                .map(|x| f64::max(0.0, x.end_time - x.start_time))
+                .collect(),
+        });
+    }
+
+    // make the arrivals relative to the first one
+    jobs.sort_by(|a, b| a.arrival.total_cmp(&b.arrival));
+    if let Some(first_arrival) = jobs.first().map(|x| x.arrival) {
+        for job in &mut jobs {
+            job.arrival -= first_arrival;
+        }
+    }
+    Ok(jobs)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_alibaba_parse_task_name() {
+        assert_eq!(Some((1, vec![])), parse_task_name("M1"));
+        assert_eq!(Some((4, vec![1, 2, 3])), parse_task_name("R4_1_2_3"));
+        assert_eq!(Some((12, vec![10])), parse_task_name("J12_10"));
+        assert_eq!(None, parse_task_name("task_Nzg3ODAwNDgzMTAwNTc2NTQ2Mw=="));
+        assert_eq!(None, parse_task_name("MergeTask"));
+    }
+
+    #[test]
+    fn test_alibaba_read_batch_tasks() -> anyhow::Result<()> {
+        let node_types = crate::cluster::NodeType::from_file("data/node_types.csv")?;
+        let jobs = read_batch_tasks("data/batch_task_sample.csv", 100.0, 10.0, true, &node_types)?;
+        assert_eq!(
+            vec!["j_1", "j_2", "j_3", "j_4", "j_5"],
+            jobs.iter().map(|x| x.name.as_str()).collect::<Vec<&str>>()
+        );
+        assert_eq!(
+            vec![0.0, 12.0, 90.0, 190.0, 300.0],
+            jobs.iter().map(|x| x.arrival).collect::<Vec<f64>>()
+        );
+        assert_eq!(
+            vec![61.0, 130.0, 125.0, 2.0, 130.0],
+            jobs.iter().map(|x| x.lifetime).collect::<Vec<f64>>()
+        );
+        assert_eq!(
+            vec![(2, 1), (4, 3), (1, 0), (1, 0), (5, 4)],
+            jobs.iter()
+                .map(|x| (x.job.graph.node_count(), x.job.graph.edge_count()))
+                .collect::<Vec<(usize, usize)>>()
+        );
+
+        // J3_1_2 depends on M1 and M2, R4_3 on J3_1_2
+        let job = &jobs[1];
+        assert_eq!(vec![58.0, 37.0, 60.0, 12.0], job.durations);
+        assert_eq!(450, job.job.total_cpu());
+        assert_eq!(20 + 59 + 118 + 49, job.job.total_state_size());
+        assert_eq!(2 + 6 + 12, job.job.total_arg_size());
+        assert!(job.job.graph.contains_edge(0.into(), 2.into()));
+        assert!(job.job.graph.contains_edge(1.into(), 2.into()));
+        assert!(job.job.graph.contains_edge(2.into(), 3.into()));
+
+        // with the resources of all the instances of M1 and M2
+        let jobs_all_instances = read_batch_tasks(
+            "data/batch_task_sample.csv",
+            100.0,
+            10.0,
+            false,
+            &node_types,
+        )?;
+        assert_eq!(jobs.len(), jobs_all_instances.len());
+        let job = &jobs_all_instances[1];
+        assert_eq!(4 * 50 + 2 * 100 + 200 + 100, job.job.total_cpu());
+        assert_eq!(80 + 118 + 118 + 49, job.job.total_state_size());
+        assert_eq!(8 + 12 + 12, job.job.total_arg_size());
+        assert_eq!(
+            jobs[4].job.total_cpu(),
+            jobs_all_instances[4].job.total_cpu()
+        );
+
+        // tasks start when their dependencies end, as in the trace
+        assert_eq!(
+            vec![40.0, 19.0, 21.0, 101.0, 130.0],
+            jobs[4].job.finish_times(&jobs[4].durations)
+        );
+
+        assert!(read_batch_tasks("data/non-existing.csv", 1.0, 1.0, false, &node_types).is_err());
+
+        // negative or invalid resources or instances
+        let filename = std::env::temp_dir().join(format!(
+            "stateful-faas-sim-batch-task-{}.csv",
+            std::process::id()
+        ));
+        for task in [
+            "1,j_1,1,Terminated,86510,86540,-100.0,0.39",
+            "1,j_1,1,Terminated,86510,86540,100.0,NaN",
+            "1,j_1,1,Terminated,86510,86540,100.0,abc",
+            "0,j_1,1,Terminated,86510,86540,100.0,0.39",
+            "x,j_1,1,Terminated,86510,86540,100.0,0.39",
+        ] {
+            std::fs::write(&filename, format!("M1,{}\n", task))?;
+            let res = read_batch_tasks(filename.to_str().unwrap(), 1.0, 1.0, false, &node_types);
+            assert!(res.is_err(), "{}", task);
+        }
+
+        // the instances together may not fit into any node type
+        std::fs::write(&filename, "M1,20,j_1,1,Terminated,86510,86540,100.0,0.39\n")?;
+        for (single_instance, num_jobs) in [(true, 1), (false, 0)] {
+            let jobs = read_batch_tasks(
+                filename.to_str().unwrap(),
+                1.0,
+                1.0,
+                single_instance,
+                &node_types,
+            )?;
+            assert_eq!(num_jobs, jobs.len());
+        }
+        std::fs::remove_file(&filename)?;
+        Ok(())
+    }
+}
@@ -0,0 +1,393 @@
+use rand::{distributions::Distribution, Rng, SeedableRng};
+
+/// Profile over time of the rate of job arrivals, relative to the
+/// average rate, i.e., the inverse of the average interarrival time.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ArrivalProfile {
+    /// The rate is constant.
+    Constant,
+    /// The rate follows a sinusoid with given relative amplitude and
+    /// period, in s, with the minimum at the beginning of every period,
+    /// e.g., midnight for a period of one day.
+    Diurnal { amplitude: f64, period: f64 },
+    /// The rate is piecewise-constant: each element is the start time,
+    /// in s, from which the rate is multiplied by the given value, until
+    /// the start time of the next element or forever for the last one.
+    Piecewise(Vec<(f64, f64)>),
+}
+
+impl ArrivalProfile {
+    /// Read a piecewise-constant profile from a file with one
+    /// start-time,multiplier pair per line.
+    pub fn from_file(filename: &str) -> anyhow::Result<Self> {
+        let mut rates = vec![];
+        for (i, line) in std::fs::read_to_string(filename)?.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let tokens = line.split(',').map(|x| x.trim()).collect::<Vec<&str>>();
+            anyhow::ensure!(tokens.len() == 2, format!("invalid line {}", i));
+            rates.push((tokens[0].parse::<f64>()?, tokens[1].parse::<f64>()?));
+        }
+        Ok(Self::Piecewise(rates))
+    }
+
+    /// Return the rate multiplier at the given time.
+    pub fn multiplier(&self, time: f64) -> f64 {
+        match self {
+            Self::Constant => 1.0,
+            Self::Diurnal { amplitude, period } => {
+                1.0 - amplitude * (2.0 * std::f64::consts::PI * time / period).cos()
+            }
+            Self::Piecewise(rates) => {
+                let pos = rates.partition_point(|(start, _)| *start <= time);
+                match pos {
+                    0 => 0.0,
+                    _ => rates[pos - 1].1,
+                }
+            }
+        }
+    }
+
+    /// Return the maximum rate multiplier.
+    fn max_multiplier(&self) -> f64 {
+        match self {
+            Self::Constant => 1.0,
+            Self::Diurnal { amplitude, .. } => 1.0 + amplitude,
+            Self::Piecewise(rates) => rates.iter().map(|(_, x)| *x).fold(0.0, f64::max),
+        }
+    }
+
+    fn validate(&self) -> anyhow::Result<()> {
+        match self {
+            Self::Constant => {}
+            Self::Diurnal { amplitude, period } => {
+                anyhow::ensure!(
+                    (0.0..=1.0).contains(amplitude),
+                    "diurnal amplitude not in [0,1]"
+                );
+                anyhow::ensure!(*period > 0.0, "vanishing diurnal period");
+            }
+            Self::Piecewise(rates) => {
+                anyhow::ensure!(
+                    rates.first().is_some_and(|x| x.0 == 0.0),
+                    "piecewise rates must start at time 0"
+                );
+                anyhow::ensure!(
+                    rates.windows(2).all(|x| x[0].0 < x[1].0),
+                    "piecewise rates not in increasing order of time"
+                );
+                anyhow::ensure!(rates.iter().all(|x| x.1 >= 0.0), "negative piecewise rate");
+                anyhow::ensure!(self.max_multiplier() > 0.0, "vanishing piecewise rates");
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Distribution of the interval between consecutive job arrivals.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InterarrivalDistribution {
+    /// Exponential, i.e., the arrivals are a Poisson process.
+    Exponential,
+    /// Empirical, from the job_interval_dist.dat file, ignoring the
+    /// average interarrival time of the configuration.
+    Empirical,
+    /// Empirical, from the job_interval_dist.dat file, rescaled so that
+    /// its mean is the average interarrival time of the configuration.
+    EmpiricalRescaled,
+}
+
+impl std::str::FromStr for InterarrivalDistribution {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> anyhow::Result<Self> {
+        match s {
+            "exponential" => Ok(Self::Exponential),
+            "empirical" => Ok(Self::Empirical),
+            "empirical-rescaled" => Ok(Self::EmpiricalRescaled),
+            _ => Err(anyhow::anyhow!("unknown interarrival distribution: {}", s)),
+        }
+    }
+}
+
+impl std::fmt::Display for InterarrivalDistribution {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Exponential => write!(f, "exponential"),
+            Self::Empirical => write!(f, "empirical"),
+            Self::EmpiricalRescaled => write!(f, "empirical-rescaled"),
+        }
+    }
+}
+
+/// Generator of the job arrival times.
+///
+/// With a time-varying profile, the arrivals are a non-homogeneous
+/// Poisson process obtained by thinning a homogeneous one at the
+/// maximum rate: a candidate arrival at time t is accepted with
+/// probability equal to the ratio between the rate at t and the maximum.
+pub struct ArrivalProcess {
+    profile: ArrivalProfile,
+    max_multiplier: f64,
+    rng: rand::rngs::StdRng,
+    interarrival_rv: rand_distr::Exp<f64>,
+    /// Empirical interarrival distribution and multiplier of its samples.
+    empirical_rv: Option<(crate::rv_histo::RvHisto, f64)>,
+}
+
+impl ArrivalProcess {
+    pub fn new(
+        profile: ArrivalProfile,
+        distribution: InterarrivalDistribution,
+        job_interarrival: f64,
+        seed: u64,
+    ) -> anyhow::Result<Self> {
+        anyhow::ensure!(
+            job_interarrival > 0.0,
+            "vanishing avg job interarrival time"
+        );
+        profile.validate()?;
+        let empirical_rv = match distribution {
+            InterarrivalDistribution::Exponential => None,
+            InterarrivalDistribution::Empirical | InterarrivalDistribution::EmpiricalRescaled => {
+                anyhow::ensure!(
+                    profile == ArrivalProfile::Constant,
+                    "an arrival profile requires exponential interarrivals"
+                );
+                let rv = crate::rv_histo::RvHisto::from_file(seed, "data/job_interval_dist.dat")?;
+                let multiplier = match distribution {
+                    InterarrivalDistribution::EmpiricalRescaled => {
+                        anyhow::ensure!(rv.mean() > 0.0, "vanishing empirical interarrival");
+                        job_interarrival / rv.mean()
+                    }
+                    _ => 1.0,
+                };
+                Some((rv, multiplier))
+            }
+        };
+        let max_multiplier = profile.max_multiplier();
+        Ok(Self {
+            profile,
+            max_multiplier,
+            rng: rand::rngs::StdRng::seed_from_u64(seed),
+            interarrival_rv: rand_distr::Exp::new(max_multiplier / job_interarrival)?,
+            empirical_rv,
+        })
+    }
+
+    /// Return the time of the next arrival after the given time, which
+    /// is infinite if there are no more arrivals.
+    pub fn next(&mut self, now: f64) -> f64 {
+        if let Some((rv, multiplier)) = &mut self.empirical_rv {
+            return now + *multiplier * rv.sample();
+        }
+        if self.profile == ArrivalProfile::Constant {
+            return now + self.interarrival_rv.sample(&mut self.rng);
+        }
+        let mut time = now;
+        loop {
+            if let ArrivalProfile::Piecewise(rates) = &self.profile {
+                let last = rates.last().unwrap();
+                if time >= last.0 && last.1 == 0.0 {
+                    return f64::INFINITY;
+                }
+            }
+            time += self.interarrival_rv.sample(&mut self.rng);
+            if self.rng.gen::<f64>() * self.max_multiplier < self.profile.multiplier(time) {
+                return time;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Return the number of arrivals in consecutive intervals of given duration.
+    fn count_arrivals(
+        process: &mut ArrivalProcess,
+        interval: f64,
+        num_intervals: usize,
+    ) -> Vec<usize> {
+        let mut counts = vec![0; num_intervals];
+        let mut now = 0.0;
+        loop {
+            now = process.next(now);
+            let index = (now / interval) as usize;
+            if index >= num_intervals {
+                break;
+            }
+            counts[index] += 1;
+        }
+        counts
+    }
+
+    #[test]
+    fn test_arrival_profile_multiplier() {
+        let profile = ArrivalProfile::Diurnal {
+            amplitude: 0.5,
+            period: 100.0,
+        };
+        assert!((profile.multiplier(0.0) - 0.5).abs() < 1e-9);
+        assert!((profile.multiplier(50.0) - 1.5).abs() < 1e-9);
+        assert!((profile.multiplier(100.0) - 0.5).abs() < 1e-9);
+
+        let profile = ArrivalProfile::Piecewise(vec![(0.0, 1.0), (10.0, 0.0), (20.0, 3.0)]);
+        assert_eq!(1.0, profile.multiplier(0.0));
+        assert_eq!(1.0, profile.multiplier(9.9));
+        assert_eq!(0.0, profile.multiplier(10.0));
+        assert_eq!(3.0, profile.multiplier(1e9));
+
+        assert!(ArrivalProfile::Piecewise(vec![]).validate().is_err());
+        assert!(ArrivalProfile::Piecewise(vec![(1.0, 1.0)])
+            .validate()
+            .is_err());
+        assert!(ArrivalProfile::Piecewise(vec![(0.0, 1.0), (0.0, 2.0)])
+            .validate()
+            .is_err());
+        assert!(ArrivalProfile::Piecewise(vec![(0.0, 0.0)])
+            .validate()
+            .is_err());
+        assert!(ArrivalProfile::Diurnal {
+            amplitude: 1.5,
+            period: 1.0
+        }
+        .validate()
+        .is_err());
+    }
+
+    #[test]
+    fn test_arrival_profile_from_file() -> anyhow::Result<()> {
+        let profile = ArrivalProfile::from_file("data/arrival_rates.csv")?;
+        profile.validate()?;
+        match &profile {
+            ArrivalProfile::Piecewise(rates) => assert_eq!(24, rates.len()),
+            _ => panic!("unexpected profile: {:?}", profile),
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_arrival_process_constant() -> anyhow::Result<()> {
+        // same arrivals as an exponential distribution with the same seed
+        let mut process = ArrivalProcess::new(
+            ArrivalProfile::Constant,
+            InterarrivalDistribution::Exponential,
+            2.0,
+            42,
+        )?;
+        let mut rng = rand::rngs::StdRng::seed_from_u64(42);
+        let rv = rand_distr::Exp::new(0.5)?;
+        let mut now = 0.0;
+        for _ in 0..100 {
+            let expected = now + rv.sample(&mut rng);
+            now = process.next(now);
+            assert_eq!(expected, now);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_arrival_process_diurnal() -> anyhow::Result<()> {
+        let profile = ArrivalProfile::Diurnal {
+            amplitude: 0.8,
+            period: 1000.0,
+        };
+        let mut process =
+            ArrivalProcess::new(profile, InterarrivalDistribution::Exponential, 0.1, 42)?;
+        let counts = count_arrivals(&mut process, 100.0, 100);
+
+        // compare the arrivals in every tenth of the period, across all
+        // the periods, with the integral of the rate in that interval
+        for phase in 0..10 {
+            let measured = (phase..100).step_by(10).map(|x| counts[x]).sum::<usize>() as f64;
+            let (t0, t1) = (100.0 * phase as f64, 100.0 * (phase + 1) as f64);
+            let integral = (t1 - t0)
+                - 0.8 * 1000.0 / (2.0 * std::f64::consts::PI)
+                    * ((2.0 * std::f64::consts::PI * t1 / 1000.0).sin()
+                        - (2.0 * std::f64::consts::PI * t0 / 1000.0).sin());
+            let expected = 10.0 * integral / 0.1;
+            assert!(
+                (measured - expected).abs() < 0.05 * expected,
+                "{} {} {}",
+                phase,
+                measured,
+                expected
+            );
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_arrival_process_piecewise() -> anyhow::Result<()> {
+        let profile = ArrivalProfile::Piecewise(vec![(0.0, 1.0), (10000.0, 0.0), (20000.0, 4.0)]);
+        let mut process =
+            ArrivalProcess::new(profile, InterarrivalDistribution::Exponential, 0.5, 42)?;
+        let counts = count_arrivals(&mut process, 10000.0, 3);
+        assert!((counts[0] as f64 - 20000.0).abs() < 0.03 * 20000.0);
+        assert_eq!(0, counts[1]);
+        assert!((counts[2] as f64 - 80000.0).abs() < 0.03 * 80000.0);
+
+        // no more arrivals
+        let profile = ArrivalProfile::Piecewise(vec![(0.0, 1.0), (10.0, 0.0)]);
+        let mut process =
+            ArrivalProcess::new(profile, InterarrivalDistribution::Exponential, 1.0, 42)?;
+        let mut now = 0.0_f64;
+        while now.is_finite() {
+            assert!(now < 10.0);
+            now = process.next(now);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_arrival_process_empirical() -> anyhow::Result<()> {
+        for (distribution, expected) in [
+            (InterarrivalDistribution::Empirical, 0.205),
+            (InterarrivalDistribution::EmpiricalRescaled, 2.0),
+        ] {
+            let mut process = ArrivalProcess::new(ArrivalProfile::Constant, distribution, 2.0, 42)?;
+            let mut now = 0.0;
+            for _ in 0..100000 {
+                let next = process.next(now);
+                assert!(next >= now);
+                now = next;
+            }
+            let measured = now / 100000.0;
+            assert!(
+                (measured - expected).abs() < 0.03 * expected,
+                "{}",
+                measured
+            );
+        }
+
+        // only with a constant profile
+        assert!(ArrivalProcess::new(
+            ArrivalProfile::Diurnal {
+                amplitude: 0.5,
+                period: 1.0
+            },
+            InterarrivalDistribution::Empirical,
+            1.0,
+            42
+        )
+        .is_err());
+
+        for distribution in [
+            InterarrivalDistribution::Exponential,
+            InterarrivalDistribution::Empirical,
+            InterarrivalDistribution::EmpiricalRescaled,
+        ] {
+            assert_eq!(
+                distribution,
+                distribution
+                    .to_string()
+                    .parse::<InterarrivalDistribution>()?
+            );
+        }
+        Ok(())
+    }
+}
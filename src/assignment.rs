@@ -0,0 +1,231 @@
+/// Solve the assignment problem on a dense matrix of non-negative weights
+/// with the Hungarian algorithm, maximizing the total weight.
+///
+/// Return the column assigned to each row: if there are more rows than
+/// columns, then some rows remain unassigned.
+pub fn max_weight_assignment(weights: &[Vec<u64>]) -> Vec<Option<usize>> {
+    let num_rows = weights.len();
+    let num_cols = weights.first().map_or(0, |x| x.len());
+    if num_rows == 0 || num_cols == 0 {
+        return vec![None; num_rows];
+    }
+    assert!(weights.iter().all(|x| x.len() == num_cols));
+
+    // the algorithm below requires no more rows than columns
+    if num_rows > num_cols {
+        let transposed = (0..num_cols)
+            .map(|col| (0..num_rows).map(|row| weights[row][col]).collect())
+            .collect::<Vec<Vec<u64>>>();
+        let mut ret = vec![None; num_rows];
+        for (col, row) in max_weight_assignment(&transposed).into_iter().enumerate() {
+            ret[row.unwrap()] = Some(col);
+        }
+        return ret;
+    }
+
+    // minimize the cost, defined as the maximum weight minus the weight,
+    // with potentials u (rows) and v (columns) and 1-based indices
+    let max_weight = weights.iter().flatten().max().copied().unwrap() as i64;
+    let cost = |row: usize, col: usize| max_weight - weights[row - 1][col - 1] as i64;
+    let mut u = vec![0_i64; num_rows + 1];
+    let mut v = vec![0_i64; num_cols + 1];
+    let mut p = vec![0_usize; num_cols + 1]; // row assigned to each column
+    let mut way = vec![0_usize; num_cols + 1];
+    for row in 1..=num_rows {
+        p[0] = row;
+        let mut col0 = 0;
+        let mut min_v = vec![i64::MAX; num_cols + 1];
+        let mut used = vec![false; num_cols + 1];
+        loop {
+            used[col0] = true;
+            let row0 = p[col0];
+            let mut delta = i64::MAX;
+            let mut col1 = 0;
+            for col in 1..=num_cols {
+                if !used[col] {
+                    let cur = cost(row0, col) - u[row0] - v[col];
+                    if cur < min_v[col] {
+                        min_v[col] = cur;
+                        way[col] = col0;
+                    }
+                    if min_v[col] < delta {
+                        delta = min_v[col];
+                        col1 = col;
+                    }
+                }
+            }
+            for col in 0..=num_cols {
+                if used[col] {
+                    u[p[col]] += delta;
+                    v[col] -= delta;
+                } else {
+                    min_v[col] -= delta;
+                }
+            }
+            col0 = col1;
+            if p[col0] == 0 {
+                break;
+            }
+        }
+        loop {
+            let col1 = way[col0];
+            p[col0] = p[col1];
+            col0 = col1;
+            if col0 == 0 {
+                break;
+            }
+        }
+    }
+
+    let mut ret = vec![None; num_rows];
+    for col in 1..=num_cols {
+        if p[col] != 0 {
+            ret[p[col] - 1] = Some(col - 1);
+        }
+    }
+    ret
+}
+
+/// Find a matching between rows and columns maximizing the total weight,
+/// where the weights are given as a sparse list of (row, column, weight).
+///
+/// The problem is decomposed into the connected components of the
+/// bipartite graph with an edge for every positive weight, each solved
+/// with [`max_weight_assignment`].
+///
+/// Return the column matched to each row, only if with positive weight.
+pub fn max_weight_matching(
+    num_rows: usize,
+    num_cols: usize,
+    weights: &[(usize, usize, u64)],
+) -> Vec<Option<usize>> {
+    // find the connected components with union-find, where
+    // rows are 0..num_rows and columns num_rows..num_rows+num_cols
+    let mut parent = (0..num_rows + num_cols).collect::<Vec<usize>>();
+    fn find(parent: &mut [usize], x: usize) -> usize {
+        let mut root = x;
+        while parent[root] != root {
+            root = parent[root];
+        }
+        let mut x = x;
+        while parent[x] != root {
+            let next = parent[x];
+            parent[x] = root;
+            x = next;
+        }
+        root
+    }
+    for (row, col, weight) in weights {
+        assert!(*row < num_rows && *col < num_cols);
+        if *weight > 0 {
+            let a = find(&mut parent, *row);
+            let b = find(&mut parent, num_rows + col);
+            parent[a] = b;
+        }
+    }
+
+    // collect the rows and columns of each component, in order
+    let mut components = std::collections::BTreeMap::new();
+    for (row, col, weight) in weights {
+        if *weight > 0 {
+            let root = find(&mut parent, *row);
+            let entry = components.entry(root).or_insert((vec![], vec![], vec![]));
+            entry.0.push(*row);
+            entry.1.push(*col);
+            entry.2.push((*row, *col, *weight));
+        }
+    }
+
+    let mut ret = vec![None; num_rows];
+    for (_root, (mut rows, mut cols, edges)) in components {
+        rows.sort();
+        rows.dedup();
+        cols.sort();
+        cols.dedup();
+        let mut dense = vec![vec![0; cols.len()]; rows.len()];
+        for (row, col, weight) in edges {
+            let i = rows.binary_search(&row).unwrap();
+            let j = cols.binary_search(&col).unwrap();
+            dense[i][j] += weight;
+        }
+        for (i, j) in max_weight_assignment(&dense).into_iter().enumerate() {
+            if let Some(j) = j {
+                if dense[i][j] > 0 {
+                    ret[rows[i]] = Some(cols[j]);
+                }
+            }
+        }
+    }
+    ret
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn total_weight(weights: &[Vec<u64>], assignment: &[Option<usize>]) -> u64 {
+        assignment
+            .iter()
+            .enumerate()
+            .filter_map(|(row, col)| col.map(|col| weights[row][col]))
+            .sum()
+    }
+
+    #[test]
+    fn test_assignment_dense() {
+        let weights = vec![vec![7, 5, 0], vec![8, 1, 0], vec![0, 6, 2]];
+        let assignment = max_weight_assignment(&weights);
+        assert_eq!(vec![Some(1), Some(0), Some(2)], assignment);
+        assert_eq!(15, total_weight(&weights, &assignment));
+
+        // more rows than columns
+        let weights = vec![vec![1], vec![3], vec![2]];
+        assert_eq!(vec![None, Some(0), None], max_weight_assignment(&weights));
+
+        // more columns than rows
+        let weights = vec![vec![1, 3, 2]];
+        assert_eq!(vec![Some(1)], max_weight_assignment(&weights));
+    }
+
+    #[test]
+    fn test_assignment_brute_force() {
+        // compare with all the permutations on small random matrices
+        use rand::{Rng, SeedableRng};
+        let mut rng = rand::rngs::StdRng::seed_from_u64(42);
+        fn best(weights: &[Vec<u64>], row: usize, used: &mut Vec<bool>) -> u64 {
+            if row == weights.len() {
+                return 0;
+            }
+            let mut ret = 0;
+            for col in 0..used.len() {
+                if !used[col] {
+                    used[col] = true;
+                    ret = ret.max(weights[row][col] + best(weights, row + 1, used));
+                    used[col] = false;
+                }
+            }
+            ret
+        }
+        for _ in 0..100 {
+            let n = rng.gen_range(1..6);
+            let weights = (0..n)
+                .map(|_| (0..n).map(|_| rng.gen_range(0..10)).collect())
+                .collect::<Vec<Vec<u64>>>();
+            let assignment = max_weight_assignment(&weights);
+            assert_eq!(
+                best(&weights, 0, &mut vec![false; n]),
+                total_weight(&weights, &assignment)
+            );
+        }
+    }
+
+    #[test]
+    fn test_assignment_sparse() {
+        let matching = max_weight_matching(
+            4,
+            5,
+            &[(0, 4, 10), (1, 4, 20), (1, 3, 5), (2, 0, 1), (3, 1, 0)],
+        );
+        assert_eq!(vec![None, Some(4), Some(0), None], matching);
+    }
+}
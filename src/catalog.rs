@@ -0,0 +1,117 @@
+use rand::{distributions::Distribution, SeedableRng};
+
+use crate::job::Job;
+
+/// Catalog of jobs, e.g., real production workflows, from which the
+/// jobs arriving are drawn at random with given mix probabilities.
+pub struct JobCatalog {
+    jobs: Vec<Job>,
+    mix: rand::distributions::WeightedIndex<f64>,
+    rng: rand::rngs::StdRng,
+}
+
+impl JobCatalog {
+    /// Create a catalog with the given jobs and their mix probabilities,
+    /// which are normalized so that they sum to one.
+    /// Whether the tasks fit into the nodes is checked by the simulation,
+    /// see [`Job::validate`].
+    pub fn new(seed: u64, jobs: Vec<(Job, f64)>) -> anyhow::Result<Self> {
+        anyhow::ensure!(!jobs.is_empty(), "empty job catalog");
+        let (jobs, probabilities): (Vec<Job>, Vec<f64>) = jobs.into_iter().unzip();
+        anyhow::ensure!(
+            jobs.iter()
+                .all(|x| x.graph.node_count() <= crate::cluster::MAX_TASKS_PER_JOB),
+            "too many tasks in a job of the catalog"
+        );
+        anyhow::ensure!(
+            probabilities.iter().all(|x| x.is_finite() && *x >= 0.0),
+            "invalid mix probabilities"
+        );
+        Ok(Self {
+            jobs,
+            mix: rand::distributions::WeightedIndex::new(probabilities)?,
+            rng: rand::rngs::StdRng::seed_from_u64(seed),
+        })
+    }
+
+    /// Read the catalog from a file with one job per line: name of the file
+    /// describing the job, relative to the directory of the catalog, and
+    /// mix probability. The jobs are read with [`Job::from_file`].
+    /// Empty lines and those beginning with `#` are ignored.
+    pub fn from_file(seed: u64, filename: &str) -> anyhow::Result<Self> {
+        let directory = std::path::Path::new(filename)
+            .parent()
+            .unwrap_or(std::path::Path::new(""));
+        let mut jobs = vec![];
+        for (i, line) in std::fs::read_to_string(filename)?.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let tokens = line.split(',').map(|x| x.trim()).collect::<Vec<&str>>();
+            anyhow::ensure!(tokens.len() == 2, format!("invalid line {}", i));
+            let job_filename = directory.join(tokens[0]);
+            jobs.push((
+                Job::from_file(&job_filename.to_string_lossy())?,
+                tokens[1].parse::<f64>()?,
+            ));
+        }
+        Self::new(seed, jobs)
+    }
+
+    /// Return the jobs in the catalog.
+    pub fn jobs(&self) -> &[Job] {
+        &self.jobs
+    }
+
+    /// Draw a job from the catalog.
+    pub fn make(&mut self) -> Job {
+        self.jobs[self.mix.sample(&mut self.rng)].clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_catalog_from_file() -> anyhow::Result<()> {
+        let mut catalog = JobCatalog::from_file(42, "data/catalog/catalog.csv")?;
+        let num_tasks = catalog
+            .jobs()
+            .iter()
+            .map(|x| x.graph.node_count())
+            .collect::<Vec<usize>>();
+        assert_eq!(vec![4, 3], num_tasks);
+
+        // the jobs are drawn with the mix probabilities
+        let mut counts = vec![0; 2];
+        for _ in 0..10000 {
+            let job = catalog.make();
+            counts[num_tasks
+                .iter()
+                .position(|x| *x == job.graph.node_count())
+                .unwrap()] += 1;
+        }
+        assert!(
+            (counts[0] as f64 / 10000.0 - 0.7).abs() < 0.02,
+            "{:?}",
+            counts
+        );
+
+        assert!(JobCatalog::from_file(42, "data/catalog/non-existing.csv").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_catalog_mix() {
+        let job = || Job::new(vec![crate::job::Vertex::new(100, 1)], vec![]);
+        assert!(JobCatalog::new(42, vec![]).is_err());
+        assert!(JobCatalog::new(42, vec![(job(), 0.0)]).is_err());
+        assert!(JobCatalog::new(42, vec![(job(), -1.0), (job(), 2.0)]).is_err());
+        assert!(JobCatalog::new(42, vec![(job(), 0.0), (job(), 2.0)]).is_ok());
+
+        let vertices = vec![crate::job::Vertex::new(100, 1); crate::cluster::MAX_TASKS_PER_JOB + 1];
+        assert!(JobCatalog::new(42, vec![(Job::new(vertices, vec![]), 1.0)]).is_err());
+    }
+}
@@ -0,0 +1,747 @@
+use petgraph::visit::EdgeRef;
+
+/// Amount of resources requested by a task or provided by a node.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Resources {
+    /// CPU, every 100 unit means 1 core
+    pub cpu: usize,
+    /// Memory, in MB
+    pub memory: usize,
+}
+
+impl Resources {
+    pub fn new(cpu: usize, memory: usize) -> Self {
+        Self { cpu, memory }
+    }
+
+    /// Return true if this amount of resources does not exceed
+    /// the given capacity in any dimension.
+    pub fn fits_into(&self, capacity: &Resources) -> bool {
+        self.cpu <= capacity.cpu && self.memory <= capacity.memory
+    }
+}
+
+impl std::ops::Add for Resources {
+    type Output = Resources;
+
+    fn add(self, other: Resources) -> Resources {
+        Resources::new(self.cpu + other.cpu, self.memory + other.memory)
+    }
+}
+
+impl std::ops::AddAssign for Resources {
+    fn add_assign(&mut self, other: Resources) {
+        *self = *self + other;
+    }
+}
+
+impl std::ops::Sub for Resources {
+    type Output = Resources;
+
+    fn sub(self, other: Resources) -> Resources {
+        Resources::new(self.cpu - other.cpu, self.memory - other.memory)
+    }
+}
+
+impl std::ops::SubAssign for Resources {
+    fn sub_assign(&mut self, other: Resources) {
+        *self = *self - other;
+    }
+}
+
+impl std::iter::Sum for Resources {
+    fn sum<I: Iterator<Item = Resources>>(iter: I) -> Resources {
+        iter.fold(Resources::default(), |acc, x| acc + x)
+    }
+}
+
+impl std::fmt::Display for Resources {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "(cpu = {}, memory = {})", self.cpu, self.memory)
+    }
+}
+
+/// A type of processing node that can be added to the cluster.
+#[derive(Debug, Clone, PartialEq)]
+pub struct NodeType {
+    /// Name of the node type
+    pub name: String,
+    /// Resources provided by a node of this type
+    pub capacity: Resources,
+    /// Power consumed by an idle node, in W
+    pub idle_power: f64,
+    /// Power consumed by a node with full CPU load, in W
+    pub peak_power: f64,
+    /// Price of using a node of this type for one hour
+    pub hourly_price: f64,
+}
+
+impl NodeType {
+    pub fn new(
+        name: &str,
+        capacity: Resources,
+        idle_power: f64,
+        peak_power: f64,
+        hourly_price: f64,
+    ) -> Self {
+        Self {
+            name: name.to_string(),
+            capacity,
+            idle_power,
+            peak_power,
+            hourly_price,
+        }
+    }
+
+    /// Read a catalog of node types from a file with one type per line:
+    /// name,cpu,memory,idle-power,peak-power,hourly-price
+    ///
+    /// Empty lines and those starting with '#' are ignored.
+    pub fn from_file(filename: &str) -> anyhow::Result<Vec<Self>> {
+        let mut node_types = vec![];
+        for (i, line) in std::fs::read_to_string(filename)?.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let tokens = line.split(',').map(|x| x.trim()).collect::<Vec<&str>>();
+            anyhow::ensure!(tokens.len() == 6, format!("invalid line {}", i));
+            node_types.push(NodeType::new(
+                tokens[0],
+                Resources::new(tokens[1].parse::<usize>()?, tokens[2].parse::<usize>()?),
+                tokens[3].parse::<f64>()?,
+                tokens[4].parse::<f64>()?,
+                tokens[5].parse::<f64>()?,
+            ));
+        }
+        Ok(node_types)
+    }
+}
+
+#[derive(Debug)]
+pub struct Node {
+    pub node_type: usize,      // index in the catalog of node types
+    pub jobs: Vec<(u64, u32)>, // job ID, task ID within the job
+    pub reserved: Resources,   // held by tasks migrating away from this node
+    pub used: Resources,       // used by the tasks and held for migrations
+}
+
+impl Node {
+    pub fn is_active(&self) -> bool {
+        !self.jobs.is_empty() || self.reserved != Resources::default()
+    }
+}
+
+/// Resources used on a busy node.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct NodeLoad {
+    pub node_type: usize,
+    pub used: Resources,
+}
+
+/// Number of busy nodes of a given type and total resources used on them.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct TypeLoad {
+    pub busy_nodes: usize,
+    pub used: Resources,
+}
+
+/// Maximum number of tasks of a job, due to how their allocations are indexed.
+pub const MAX_TASKS_PER_JOB: usize = 1000;
+
+/// State of the system shared by the simulator and the allocation policies:
+/// the active jobs and, for stateful policies, the processing nodes with
+/// the tasks allocated to each of them.
+///
+/// The metrics on the nodes and active jobs are updated incrementally
+/// whenever they change, so that they can be queried in constant time.
+pub struct Cluster {
+    now: f64,
+    node_types: Vec<NodeType>,
+    active_jobs: std::collections::BTreeMap<u64, crate::job::Job>,
+    job_ends: std::collections::HashMap<u64, f64>, // key: job ID; value: end time
+    nodes: Vec<Node>,
+    allocations: std::collections::HashMap<u64, usize>, // key: hash of job ID and task ID; value: node ID
+
+    // incremental metrics
+    nodes_by_residual: std::collections::BTreeSet<(usize, usize)>, // residual CPU, node ID
+    type_loads: Vec<TypeLoad>,
+    migrating_nodes: usize,
+    total_demand: Resources,
+    total_arg_size: usize,
+    cross_node_traffic: usize,
+}
+
+impl Cluster {
+    /// Create an empty cluster whose nodes can be of the given types.
+    pub fn new(node_types: Vec<NodeType>) -> Self {
+        assert!(!node_types.is_empty());
+        Self {
+            now: 0.0,
+            type_loads: vec![TypeLoad::default(); node_types.len()],
+            node_types,
+            active_jobs: std::collections::BTreeMap::new(),
+            job_ends: std::collections::HashMap::new(),
+            nodes: vec![],
+            allocations: std::collections::HashMap::new(),
+            nodes_by_residual: std::collections::BTreeSet::new(),
+            migrating_nodes: 0,
+            total_demand: Resources::default(),
+            total_arg_size: 0,
+            cross_node_traffic: 0,
+        }
+    }
+
+    fn job_task_hash(job_id: u64, task_id: u32) -> u64 {
+        assert!((task_id as usize) < MAX_TASKS_PER_JOB);
+        job_id * MAX_TASKS_PER_JOB as u64 + task_id as u64
+    }
+
+    /// The current simulated time, in s.
+    pub fn now(&self) -> f64 {
+        self.now
+    }
+
+    pub(crate) fn set_now(&mut self, now: f64) {
+        self.now = now
+    }
+
+    pub fn node_types(&self) -> &[NodeType] {
+        &self.node_types
+    }
+
+    /// Return the cheapest node type whose capacity is sufficient for the
+    /// given demand, or `None` if the demand does not fit into any type.
+    pub fn cheapest_node_type(&self, demand: Resources) -> Option<usize> {
+        (0..self.node_types.len())
+            .filter(|x| demand.fits_into(&self.node_types[*x].capacity))
+            .min_by(|a, b| {
+                self.node_types[*a]
+                    .hourly_price
+                    .total_cmp(&self.node_types[*b].hourly_price)
+            })
+    }
+
+    /// Return the node type with the lowest hourly price per unit of CPU,
+    /// preferring the larger capacity, then the name first in alphabetical
+    /// order, on ties, so that the result does not depend on the order of
+    /// the types in the catalog.
+    pub fn cheapest_node_type_per_cpu(&self) -> usize {
+        (0..self.node_types.len())
+            .min_by(|a, b| {
+                let a = &self.node_types[*a];
+                let b = &self.node_types[*b];
+                (a.hourly_price / a.capacity.cpu as f64)
+                    .total_cmp(&(b.hourly_price / b.capacity.cpu as f64))
+                    .then(b.capacity.cpu.cmp(&a.capacity.cpu))
+                    .then(b.capacity.memory.cmp(&a.capacity.memory))
+                    .then(a.name.cmp(&b.name))
+            })
+            .unwrap()
+    }
+
+    /// The capacity of a processing node.
+    pub fn node_capacity(&self, node_id: usize) -> Resources {
+        self.node_types[self.nodes[node_id].node_type].capacity
+    }
+
+    /// Return the active jobs, in increasing order of job ID.
+    pub fn active_jobs(&self) -> &std::collections::BTreeMap<u64, crate::job::Job> {
+        &self.active_jobs
+    }
+
+    pub fn job(&self, job_id: u64) -> Option<&crate::job::Job> {
+        self.active_jobs.get(&job_id)
+    }
+
+    pub(crate) fn insert_job(&mut self, job_id: u64, job: crate::job::Job) {
+        self.total_demand += job.graph.node_weights().map(|x| x.demand()).sum();
+        self.total_arg_size += job.total_arg_size();
+        let _insert_ret = self.active_jobs.insert(job_id, job);
+        assert!(_insert_ret.is_none());
+    }
+
+    pub(crate) fn remove_job(&mut self, job_id: u64) {
+        let job = self.active_jobs.remove(&job_id).unwrap();
+        self.total_demand -= job.graph.node_weights().map(|x| x.demand()).sum();
+        self.total_arg_size -= job.total_arg_size();
+        self.job_ends.remove(&job_id);
+    }
+
+    /// Return the total resources requested by the tasks of the active jobs.
+    pub fn total_demand(&self) -> Resources {
+        self.total_demand
+    }
+
+    /// Return the total size of the arguments of the active jobs.
+    pub fn total_arg_size(&self) -> usize {
+        self.total_arg_size
+    }
+
+    /// Record the time at which an active job will end, in s.
+    pub(crate) fn set_job_end(&mut self, job_id: u64, end_time: f64) {
+        assert!(self.active_jobs.contains_key(&job_id));
+        self.job_ends.insert(job_id, end_time);
+    }
+
+    /// Return the time until an active job ends, in s, if known.
+    pub fn remaining_lifetime(&self, job_id: u64) -> Option<f64> {
+        self.job_ends
+            .get(&job_id)
+            .map(|end_time| f64::max(0.0, end_time - self.now))
+    }
+
+    pub fn nodes(&self) -> &[Node] {
+        &self.nodes
+    }
+
+    /// Add a new node of the given type without any task allocated
+    /// and return its ID.
+    pub fn add_node(&mut self, node_type: usize) -> usize {
+        assert!(node_type < self.node_types.len());
+        self.nodes.push(Node {
+            node_type,
+            jobs: vec![],
+            reserved: Resources::default(),
+            used: Resources::default(),
+        });
+        self.index_node(self.nodes.len() - 1);
+        self.nodes.len() - 1
+    }
+
+    /// Remove all the nodes and task allocations, e.g., to re-allocate
+    /// all the active jobs from scratch, which requires that there are
+    /// no migrations in progress.
+    pub fn clear_nodes(&mut self) {
+        assert!(self
+            .nodes
+            .iter()
+            .all(|x| x.reserved == Resources::default()));
+        self.nodes.clear();
+        self.allocations.clear();
+        self.nodes_by_residual.clear();
+        self.type_loads = vec![TypeLoad::default(); self.node_types.len()];
+        self.cross_node_traffic = 0;
+    }
+
+    /// Renumber the nodes, so that the node with ID `i` becomes the one
+    /// with ID `new_ids[i]`, where `node_types` are the types of all the
+    /// nodes after renumbering: those whose IDs are not in `new_ids`
+    /// are added without any task allocated.
+    pub fn renumber_nodes(&mut self, new_ids: &[usize], node_types: &[usize]) {
+        assert_eq!(new_ids.len(), self.nodes.len());
+        let mut nodes = node_types
+            .iter()
+            .map(|node_type| Node {
+                node_type: *node_type,
+                jobs: vec![],
+                reserved: Resources::default(),
+                used: Resources::default(),
+            })
+            .collect::<Vec<Node>>();
+        for (node, new_id) in std::mem::take(&mut self.nodes).into_iter().zip(new_ids) {
+            assert_eq!(node.node_type, nodes[*new_id].node_type);
+            assert!(!nodes[*new_id].is_active());
+            for (job_id, task_id) in &node.jobs {
+                self.allocations
+                    .insert(Cluster::job_task_hash(*job_id, *task_id), *new_id);
+            }
+            nodes[*new_id].jobs = node.jobs;
+            nodes[*new_id].reserved = node.reserved;
+            nodes[*new_id].used = node.used;
+        }
+        self.nodes = nodes;
+
+        // the nodes are indexed again, while the traffic does not change
+        self.nodes_by_residual.clear();
+        self.type_loads = vec![TypeLoad::default(); self.node_types.len()];
+        self.migrating_nodes = 0;
+        for node_id in 0..self.nodes.len() {
+            self.index_node(node_id);
+        }
+    }
+
+    /// Add the node to the incremental metrics.
+    fn index_node(&mut self, node_id: usize) {
+        let node = &self.nodes[node_id];
+        let capacity = self.node_types[node.node_type].capacity;
+        self.nodes_by_residual
+            .insert((capacity.cpu.saturating_sub(node.used.cpu), node_id));
+        if node.is_active() {
+            self.type_loads[node.node_type].busy_nodes += 1;
+            self.type_loads[node.node_type].used += node.used;
+            if node.jobs.is_empty() {
+                self.migrating_nodes += 1;
+            }
+        }
+    }
+
+    /// Remove the node from the incremental metrics.
+    fn unindex_node(&mut self, node_id: usize) {
+        let node = &self.nodes[node_id];
+        let capacity = self.node_types[node.node_type].capacity;
+        let _remove_ret = self
+            .nodes_by_residual
+            .remove(&(capacity.cpu.saturating_sub(node.used.cpu), node_id));
+        assert!(_remove_ret);
+        if node.is_active() {
+            self.type_loads[node.node_type].busy_nodes -= 1;
+            self.type_loads[node.node_type].used -= node.used;
+            if node.jobs.is_empty() {
+                self.migrating_nodes -= 1;
+            }
+        }
+    }
+
+    /// Modify a node, keeping the incremental metrics up to date.
+    fn update_node<F: FnOnce(&mut Node)>(&mut self, node_id: usize, f: F) {
+        self.unindex_node(node_id);
+        f(&mut self.nodes[node_id]);
+        self.index_node(node_id);
+    }
+
+    fn task_demand(&self, job_id: u64, task_id: u32) -> Resources {
+        self.active_jobs
+            .get(&job_id)
+            .unwrap()
+            .graph
+            .node_weight(task_id.into())
+            .unwrap()
+            .demand()
+    }
+
+    /// Return the size of the arguments exchanged between a task and
+    /// the other tasks of the same job allocated to nodes different
+    /// from the given one.
+    fn task_traffic(&self, job_id: u64, task_id: u32, node_id: usize) -> usize {
+        let graph = &self.active_jobs.get(&job_id).unwrap().graph;
+        let index = petgraph::graph::NodeIndex::new(task_id as usize);
+        graph
+            .edges_directed(index, petgraph::Outgoing)
+            .map(|edge| (edge.target(), edge.weight().arg_size))
+            .chain(
+                graph
+                    .edges_directed(index, petgraph::Incoming)
+                    .map(|edge| (edge.source(), edge.weight().arg_size)),
+            )
+            .filter(|(other, _)| *other != index)
+            .filter_map(|(other, arg_size)| {
+                self.allocation(job_id, other.index() as u32)
+                    .filter(|other_node_id| *other_node_id != node_id)
+                    .map(|_| arg_size)
+            })
+            .sum()
+    }
+
+    /// Return the node to which a task is allocated, if any.
+    pub fn allocation(&self, job_id: u64, task_id: u32) -> Option<usize> {
+        self.allocations
+            .get(&Cluster::job_task_hash(job_id, task_id))
+            .copied()
+    }
+
+    pub fn add_task(&mut self, job_id: u64, task_id: u32, node_id: usize) {
+        log::debug!("add job {}, task {}, to node {}", job_id, task_id, node_id);
+        let demand = self.task_demand(job_id, task_id);
+        self.cross_node_traffic += self.task_traffic(job_id, task_id, node_id);
+        self.update_node(node_id, |node| {
+            node.jobs.push((job_id, task_id));
+            node.used += demand;
+        });
+        self.allocations
+            .insert(Cluster::job_task_hash(job_id, task_id), node_id);
+    }
+
+    pub fn del_task(&mut self, job_id: u64, task_id: u32) {
+        let node_id = self
+            .allocations
+            .remove(&Cluster::job_task_hash(job_id, task_id))
+            .unwrap();
+        log::debug!(
+            "del job {}, task {}, from node {}",
+            job_id,
+            task_id,
+            node_id
+        );
+        let demand = self.task_demand(job_id, task_id);
+        self.cross_node_traffic -= self.task_traffic(job_id, task_id, node_id);
+        self.update_node(node_id, |node| {
+            node.jobs.retain(|(cur_job_id, cur_task_id)| {
+                *cur_job_id != job_id || *cur_task_id != task_id
+            });
+            node.used -= demand;
+        });
+    }
+
+    /// Hold resources on a node on behalf of a task migrating away from it,
+    /// until the transfer of its state is complete.
+    pub(crate) fn reserve(&mut self, node_id: usize, demand: Resources) {
+        self.update_node(node_id, |node| {
+            node.reserved += demand;
+            node.used += demand;
+        });
+    }
+
+    /// Release resources held with [`Cluster::reserve`].
+    pub(crate) fn release(&mut self, node_id: usize, demand: Resources) {
+        assert!(demand.fits_into(&self.nodes[node_id].reserved));
+        self.update_node(node_id, |node| {
+            node.reserved -= demand;
+            node.used -= demand;
+        });
+    }
+
+    /// Return the node of every task allocated, sorted by job and task IDs.
+    pub fn task_allocations(&self) -> Vec<(u64, u32, usize)> {
+        let mut allocations = self
+            .nodes
+            .iter()
+            .enumerate()
+            .flat_map(|(node_id, node)| {
+                node.jobs
+                    .iter()
+                    .map(move |(job_id, task_id)| (*job_id, *task_id, node_id))
+            })
+            .collect::<Vec<(u64, u32, usize)>>();
+        allocations.sort();
+        allocations
+    }
+
+    pub fn capacity_used(&self, node_id: usize) -> Resources {
+        self.nodes[node_id].used
+    }
+
+    /// Return the capacity residual if this node was allocated
+    /// a new task with given demand, or `None` if the new
+    /// task would not fit into the node in any of the dimensions.
+    pub fn capacity_residual(&self, node_id: usize, new_capacity: Resources) -> Option<Resources> {
+        let node_capacity = self.node_capacity(node_id);
+        let new_capacity_used = self.capacity_used(node_id) + new_capacity;
+        if new_capacity_used.fits_into(&node_capacity) {
+            Some(node_capacity - new_capacity_used)
+        } else {
+            None
+        }
+    }
+
+    /// Return the type and resources used of each of the active nodes.
+    pub fn node_loads(&self) -> Vec<NodeLoad> {
+        (0..self.nodes.len())
+            .filter(|node_id| self.nodes[*node_id].is_active())
+            .map(|node_id| NodeLoad {
+                node_type: self.nodes[node_id].node_type,
+                used: self.capacity_used(node_id),
+            })
+            .collect()
+    }
+
+    /// Return the nodes, active or not, where the given demand fits leaving
+    /// the smallest residual CPU, in increasing order of node ID.
+    pub fn best_fit_nodes(&self, demand: Resources) -> Vec<usize> {
+        let mut candidates = vec![];
+        let mut min_residual = None;
+        for (residual, node_id) in self.nodes_by_residual.range((demand.cpu, 0)..) {
+            if min_residual.is_some_and(|x| *residual > x) {
+                break;
+            }
+            if (self.nodes[*node_id].used + demand).fits_into(&self.node_capacity(*node_id)) {
+                min_residual = Some(*residual);
+                candidates.push(*node_id);
+            }
+        }
+        candidates
+    }
+
+    /// Return the busy nodes and resources used for each node type.
+    pub fn type_loads(&self) -> &[TypeLoad] {
+        &self.type_loads
+    }
+
+    /// Return the number of nodes that are busy only because of tasks
+    /// migrating away from them.
+    pub fn migrating_nodes(&self) -> usize {
+        self.migrating_nodes
+    }
+
+    /// Return the total size of the arguments exchanged between tasks
+    /// of the same job allocated to different nodes.
+    pub fn cross_node_traffic(&self) -> f64 {
+        self.cross_node_traffic as f64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::job::{Edge, Job, Vertex};
+
+    #[test]
+    fn test_cluster_allocations() {
+        let mut cluster = Cluster::new(vec![NodeType::new(
+            "default",
+            Resources::new(1000, 10),
+            50.0,
+            100.0,
+            1.0,
+        )]);
+        cluster.insert_job(
+            0,
+            Job::new(
+                vec![
+                    Vertex::new(100, 1),
+                    Vertex::new(200, 2),
+                    Vertex::new(300, 3),
+                ],
+                vec![(0, 1, Edge::new(10)), (1, 2, Edge::new(20))],
+            ),
+        );
+        let n0 = cluster.add_node(0);
+        let n1 = cluster.add_node(0);
+        cluster.add_task(0, 0, n0);
+        cluster.add_task(0, 1, n0);
+        cluster.add_task(0, 2, n1);
+
+        assert_eq!(Some(n0), cluster.allocation(0, 1));
+        assert_eq!(Resources::new(300, 3), cluster.capacity_used(n0));
+        assert_eq!(
+            Some(Resources::new(400, 4)),
+            cluster.capacity_residual(n0, Resources::new(300, 3))
+        );
+        assert_eq!(None, cluster.capacity_residual(n1, Resources::new(800, 1)));
+        assert_eq!(None, cluster.capacity_residual(n1, Resources::new(100, 8)));
+        assert_eq!(
+            vec![Resources::new(300, 3), Resources::new(300, 3)],
+            cluster
+                .node_loads()
+                .iter()
+                .map(|x| x.used)
+                .collect::<Vec<Resources>>()
+        );
+        assert_eq!(20.0, cluster.cross_node_traffic());
+
+        cluster.del_task(0, 2);
+        assert!(!cluster.nodes()[n1].is_active());
+        assert_eq!(
+            vec![NodeLoad {
+                node_type: 0,
+                used: Resources::new(300, 3)
+            }],
+            cluster.node_loads()
+        );
+        assert_eq!(None, cluster.allocation(0, 2));
+        assert_eq!(vec![(0, 0, n0), (0, 1, n0)], cluster.task_allocations());
+
+        // a node holding resources for a migrating task remains active
+        cluster.reserve(n1, Resources::new(300, 3));
+        assert!(cluster.nodes()[n1].is_active());
+        assert_eq!(Resources::new(300, 3), cluster.capacity_used(n1));
+        assert_eq!(None, cluster.capacity_residual(n1, Resources::new(800, 1)));
+        cluster.release(n1, Resources::new(300, 3));
+        assert!(!cluster.nodes()[n1].is_active());
+    }
+
+    #[test]
+    fn test_cluster_node_types() -> anyhow::Result<()> {
+        let node_types = NodeType::from_file("data/node_types.csv")?;
+        assert_eq!(3, node_types.len());
+        let cluster = Cluster::new(node_types);
+        assert_eq!(
+            Some(0),
+            cluster.cheapest_node_type(Resources::new(100, 100))
+        );
+        assert_eq!(
+            Some(1),
+            cluster.cheapest_node_type(Resources::new(1000, 100))
+        );
+        assert_eq!(
+            Some(2),
+            cluster.cheapest_node_type(Resources::new(100, 100000))
+        );
+        assert_eq!(None, cluster.cheapest_node_type(Resources::new(10000, 100)));
+
+        // same price per CPU of small and large nodes
+        assert_eq!(1, cluster.cheapest_node_type_per_cpu());
+        let mut node_types = cluster.node_types().to_vec();
+        node_types.reverse();
+        assert_eq!(1, Cluster::new(node_types).cheapest_node_type_per_cpu());
+        Ok(())
+    }
+
+    #[test]
+    fn test_cluster_incremental_metrics() {
+        use rand::{Rng, SeedableRng};
+        let mut rng = rand::rngs::StdRng::seed_from_u64(42);
+        let mut cluster = Cluster::new(vec![
+            NodeType::new("small", Resources::new(1000, 100), 50.0, 100.0, 1.0),
+            NodeType::new("large", Resources::new(2000, 200), 50.0, 100.0, 1.0),
+        ]);
+        for job_id in 0..20 {
+            cluster.insert_job(
+                job_id,
+                Job::new(
+                    vec![
+                        Vertex::new(rng.gen_range(1..500), 1),
+                        Vertex::new(rng.gen_range(1..500), 2),
+                        Vertex::new(rng.gen_range(1..500), 3),
+                    ],
+                    vec![
+                        (0, 1, Edge::new(10)),
+                        (1, 2, Edge::new(20)),
+                        (0, 2, Edge::new(5)),
+                    ],
+                ),
+            );
+        }
+        for node_type in [0, 1, 0, 1, 0] {
+            cluster.add_node(node_type);
+        }
+        for _ in 0..200 {
+            let job_id = rng.gen_range(0..20);
+            let task_id = rng.gen_range(0..3);
+            match cluster.allocation(job_id, task_id) {
+                Some(_) => cluster.del_task(job_id, task_id),
+                None => cluster.add_task(job_id, task_id, rng.gen_range(0..5)),
+            }
+
+            // compare with the metrics computed from scratch
+            let mut type_loads = vec![TypeLoad::default(); 2];
+            for node_load in cluster.node_loads() {
+                type_loads[node_load.node_type].busy_nodes += 1;
+                type_loads[node_load.node_type].used += node_load.used;
+            }
+            assert_eq!(type_loads, cluster.type_loads());
+            let mut traffic = 0;
+            for (job_id, job) in cluster.active_jobs() {
+                for edge in job.graph.edge_references() {
+                    let u = cluster.allocation(*job_id, edge.source().index() as u32);
+                    let v = cluster.allocation(*job_id, edge.target().index() as u32);
+                    if u.is_some() && v.is_some() && u != v {
+                        traffic += edge.weight().arg_size;
+                    }
+                }
+            }
+            assert_eq!(traffic as f64, cluster.cross_node_traffic());
+            let demand = Resources::new(300, 50);
+            let residuals = (0..5)
+                .filter_map(|node_id| cluster.capacity_residual(node_id, demand))
+                .map(|x| x.cpu)
+                .collect::<Vec<usize>>();
+            let expected = (0..5)
+                .filter(|node_id| {
+                    cluster
+                        .capacity_residual(*node_id, demand)
+                        .is_some_and(|x| Some(&x.cpu) == residuals.iter().min())
+                })
+                .collect::<Vec<usize>>();
+            assert_eq!(expected, cluster.best_fit_nodes(demand));
+        }
+        assert_eq!(
+            cluster.total_demand().cpu,
+            cluster
+                .active_jobs()
+                .values()
+                .map(|x| x.total_cpu())
+                .sum::<usize>()
+        );
+    }
+}
@@ -0,0 +1,92 @@
+/// Quantiles of order 0.975 of the Student's t-distribution with
+/// 1 to 30 degrees of freedom.
+const T_975: [f64; 30] = [
+    12.706, 4.303, 3.182, 2.776, 2.571, 2.447, 2.365, 2.306, 2.262, 2.228, 2.201, 2.179, 2.160,
+    2.145, 2.131, 2.120, 2.110, 2.101, 2.093, 2.086, 2.080, 2.074, 2.069, 2.064, 2.060, 2.056,
+    2.052, 2.048, 2.045, 2.042,
+];
+
+/// Return the quantile of order 0.975 of the Student's t-distribution
+/// with the given degrees of freedom, which must be positive.
+///
+/// Beyond the tabulated values, use the Cornish-Fisher expansion around
+/// the quantile of the standard normal distribution.
+pub fn t_975(dof: usize) -> f64 {
+    assert!(dof > 0);
+    if dof <= T_975.len() {
+        return T_975[dof - 1];
+    }
+    let z = 1.959964_f64;
+    let n = dof as f64;
+    z + (z.powi(3) + z) / (4.0 * n)
+        + (5.0 * z.powi(5) + 16.0 * z.powi(3) + 3.0 * z) / (96.0 * n * n)
+}
+
+/// 95% confidence interval of the mean of a metric, estimated from the
+/// values measured in independent replications.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ConfidenceInterval {
+    pub mean: f64,
+    pub half_width: f64,
+}
+
+impl ConfidenceInterval {
+    /// Estimate the confidence interval from the given values, if at least two.
+    pub fn new(values: &[f64]) -> Option<Self> {
+        if values.len() < 2 {
+            return None;
+        }
+        let mut stats = incr_stats::incr::Stats::new();
+        stats.array_update(values).ok()?;
+        let mean = stats.mean().ok()?;
+        let std_dev = stats.sample_standard_deviation().ok()?;
+        Some(Self {
+            mean,
+            half_width: t_975(values.len() - 1) * std_dev / (values.len() as f64).sqrt(),
+        })
+    }
+
+    /// Return the half-width relative to the absolute value of the mean,
+    /// which is zero if both vanish and infinite if only the mean does.
+    pub fn relative_half_width(&self) -> f64 {
+        if self.half_width == 0.0 {
+            0.0
+        } else if self.mean == 0.0 {
+            f64::INFINITY
+        } else {
+            self.half_width / self.mean.abs()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_confidence_t_975() {
+        assert_eq!(12.706, t_975(1));
+        assert_eq!(2.042, t_975(30));
+
+        // continuity with the table and convergence to the normal quantile
+        assert!((t_975(31) - 2.040).abs() < 1e-3);
+        assert!((t_975(120) - 1.980).abs() < 1e-3);
+        assert!((t_975(100000) - 1.960).abs() < 1e-3);
+    }
+
+    #[test]
+    fn test_confidence_interval() {
+        assert!(ConfidenceInterval::new(&[]).is_none());
+        assert!(ConfidenceInterval::new(&[1.0]).is_none());
+
+        let ci = ConfidenceInterval::new(&[1.0, 2.0, 3.0, 4.0, 5.0]).unwrap();
+        assert!((ci.mean - 3.0).abs() < 1e-9);
+        assert!((ci.half_width - 2.776 * 2.5_f64.sqrt() / 5_f64.sqrt()).abs() < 1e-9);
+        assert!((ci.relative_half_width() - ci.half_width / 3.0).abs() < 1e-9);
+
+        let ci = ConfidenceInterval::new(&[0.0, 0.0]).unwrap();
+        assert_eq!(0.0, ci.relative_half_width());
+        let ci = ConfidenceInterval::new(&[-1.0, 1.0]).unwrap();
+        assert_eq!(f64::INFINITY, ci.relative_half_width());
+    }
+}
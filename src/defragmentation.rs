@@ -0,0 +1,830 @@
+use petgraph::visit::IntoNodeReferences;
+
+use crate::cluster::{Cluster, Resources, TypeLoad};
+use crate::job::Job;
+use crate::policy::AllocationPolicy;
+
+/// How the active jobs are re-allocated in a defragmentation round,
+/// independently of the policy used to allocate them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DefragmentationMode {
+    /// No defragmentation.
+    None,
+    /// All the active jobs are re-allocated from scratch, see [`full_repack`].
+    Full,
+    /// The least loaded nodes are emptied within a budget, see [`incremental`].
+    Incremental,
+    /// Same as incremental, but a node is emptied only if the expected
+    /// saving exceeds the cost of migration, see [`cost_benefit`].
+    CostBenefit,
+}
+
+impl std::str::FromStr for DefragmentationMode {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> anyhow::Result<Self> {
+        match s {
+            "none" => Ok(Self::None),
+            "full" => Ok(Self::Full),
+            "incremental" => Ok(Self::Incremental),
+            "cost-benefit" => Ok(Self::CostBenefit),
+            _ => Err(anyhow::anyhow!("unknown defragmentation mode: {}", s)),
+        }
+    }
+}
+
+impl std::fmt::Display for DefragmentationMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::None => write!(f, "none"),
+            Self::Full => write!(f, "full"),
+            Self::Incremental => write!(f, "incremental"),
+            Self::CostBenefit => write!(f, "cost-benefit"),
+        }
+    }
+}
+
+/// How the remaining lifetime of a job is known when deciding migrations.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RemainingLifetime {
+    /// The actual time until the job ends.
+    Oracle,
+    /// The expected remaining lifetime: since the lifetimes are drawn from
+    /// an exponential distribution, this is the average lifetime of a job,
+    /// regardless of how long the job has been active.
+    ///
+    /// This relies on the exponential distribution being memoryless, hence
+    /// it is not valid with batch execution or jobs replayed from a trace,
+    /// whose lifetimes do not depend on the average lifetime of a job.
+    Estimated,
+}
+
+impl std::str::FromStr for RemainingLifetime {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> anyhow::Result<Self> {
+        match s {
+            "oracle" => Ok(Self::Oracle),
+            "estimated" => Ok(Self::Estimated),
+            _ => Err(anyhow::anyhow!("unknown remaining lifetime: {}", s)),
+        }
+    }
+}
+
+impl std::fmt::Display for RemainingLifetime {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Oracle => write!(f, "oracle"),
+            Self::Estimated => write!(f, "estimated"),
+        }
+    }
+}
+
+/// Order in which the tasks are re-allocated in a full repack.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RepackingOrder {
+    /// Job by job, in order of arrival, each with its tasks in order.
+    Arrival,
+    /// Task by task, in decreasing order of CPU request.
+    TaskCpu,
+    /// Job by job, in decreasing order of total CPU request.
+    JobCpu,
+    /// Job by job, in decreasing order of total state size.
+    JobState,
+    /// Job by job, in decreasing order of remaining lifetime, known
+    /// with an oracle.
+    RemainingLifetime,
+}
+
+impl std::str::FromStr for RepackingOrder {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> anyhow::Result<Self> {
+        match s {
+            "arrival" => Ok(Self::Arrival),
+            "task-cpu" => Ok(Self::TaskCpu),
+            "job-cpu" => Ok(Self::JobCpu),
+            "job-state" => Ok(Self::JobState),
+            "remaining-lifetime" => Ok(Self::RemainingLifetime),
+            _ => Err(anyhow::anyhow!("unknown repacking order: {}", s)),
+        }
+    }
+}
+
+impl std::fmt::Display for RepackingOrder {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Arrival => write!(f, "arrival"),
+            Self::TaskCpu => write!(f, "task-cpu"),
+            Self::JobCpu => write!(f, "job-cpu"),
+            Self::JobState => write!(f, "job-state"),
+            Self::RemainingLifetime => write!(f, "remaining-lifetime"),
+        }
+    }
+}
+
+/// Maximum migrations in a single round of incremental defragmentation.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct MigrationBudget {
+    /// Maximum number of tasks migrated, unlimited if `None`
+    pub tasks: Option<usize>,
+    /// Maximum size of the state migrated, in MB, unlimited if `None`
+    pub state: Option<usize>,
+}
+
+impl MigrationBudget {
+    /// Return true if migrating the given tasks and state would not exceed
+    /// the budget, in addition to those already migrated.
+    fn allows(&self, num_migrations: usize, migration_traffic: usize) -> bool {
+        self.tasks.is_none_or(|x| num_migrations <= x)
+            && self.state.is_none_or(|x| migration_traffic <= x)
+    }
+}
+
+/// Return the ratio between the busy nodes and the minimum number of nodes
+/// of the same types that could host the resources used on them, i.e.,
+/// for each node type, the resources used on its nodes divided by its
+/// capacity, in each dimension.
+///
+/// The index is 1 if the nodes of every type are packed optimally, or if
+/// there are no busy nodes, and it grows with fragmentation.
+pub fn fragmentation_index(cluster: &Cluster, type_loads: &[TypeLoad]) -> f64 {
+    let mut busy_nodes = 0;
+    let mut min_nodes = 0;
+    for (node_type, type_load) in cluster.node_types().iter().zip(type_loads.iter()) {
+        busy_nodes += type_load.busy_nodes;
+        min_nodes += usize::max(
+            type_load.used.cpu.div_ceil(node_type.capacity.cpu),
+            type_load.used.memory.div_ceil(node_type.capacity.memory),
+        );
+    }
+    match busy_nodes {
+        0 => 1.0,
+        _ => busy_nodes as f64 / usize::max(1, min_nodes) as f64,
+    }
+}
+
+/// Parameters to compare the energy saved by migrating tasks with the
+/// energy consumed to transfer their state.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MigrationCost {
+    /// Rate at which a job is executed within its lifetime, in Hz
+    pub invocation_rate: f64,
+    /// Energy consumed to transfer one byte between two nodes, in uJ
+    pub network_energy: f64,
+    /// Average lifetime of a job, in s
+    pub job_lifetime: f64,
+    /// How the remaining lifetime of a job is known
+    pub remaining_lifetime: RemainingLifetime,
+}
+
+impl MigrationCost {
+    fn remaining_lifetime(&self, cluster: &Cluster, job_id: u64) -> f64 {
+        match self.remaining_lifetime {
+            RemainingLifetime::Oracle => cluster
+                .remaining_lifetime(job_id)
+                .unwrap_or_else(|| panic!("unknown end time of job {}", job_id)),
+            RemainingLifetime::Estimated => self.job_lifetime,
+        }
+    }
+}
+
+/// Configuration of the defragmentation of the active jobs.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Defragmentation {
+    pub mode: DefragmentationMode,
+    pub repacking_order: RepackingOrder,
+    pub migration_budget: MigrationBudget,
+    pub migration_cost: MigrationCost,
+}
+
+impl Defragmentation {
+    pub fn from_config(config: &crate::simulation::Config) -> Self {
+        Self {
+            mode: config.defragmentation_mode,
+            repacking_order: config.repacking_order,
+            migration_budget: config.migration_budget,
+            migration_cost: MigrationCost {
+                invocation_rate: config.job_invocation_rate,
+                network_energy: config.network_energy,
+                job_lifetime: config.job_lifetime,
+                remaining_lifetime: config.remaining_lifetime,
+            },
+        }
+    }
+
+    /// Optimize the allocation of the active jobs, using the policy
+    /// to re-allocate them in a full repack.
+    ///
+    /// Return the traffic due to the migration of task state and
+    /// the number of migrations.
+    pub fn run<P: AllocationPolicy + ?Sized>(
+        &self,
+        policy: &mut P,
+        cluster: &mut Cluster,
+    ) -> (usize, usize) {
+        match self.mode {
+            DefragmentationMode::None => (0, 0),
+            DefragmentationMode::Full => full_repack(policy, cluster, self.repacking_order),
+            DefragmentationMode::Incremental => incremental(cluster, &self.migration_budget),
+            DefragmentationMode::CostBenefit => {
+                cost_benefit(cluster, &self.migration_budget, &self.migration_cost)
+            }
+        }
+    }
+}
+
+/// Re-allocate all the active jobs from scratch with the given policy,
+/// in the given order.
+///
+/// Only the tasks currently allocated are re-allocated, since with batch
+/// execution some tasks of an active job may not have started yet or
+/// may have already finished.
+///
+/// Since the nodes obtained are numbered in the order in which they are
+/// created, they are then matched to the previous nodes of the same type
+/// so as to maximize the total size of the state of the tasks that remain
+/// in the same node, i.e., which do not need to be migrated.
+///
+/// Return the traffic due to the migration of task state and
+/// the number of migrations.
+pub fn full_repack<P: AllocationPolicy + ?Sized>(
+    policy: &mut P,
+    cluster: &mut Cluster,
+    order: RepackingOrder,
+) -> (usize, usize) {
+    // save the previous allocation to compute the number of migrations
+    // and the traffic due to the migration of task state
+    let jobs = cluster
+        .active_jobs()
+        .iter()
+        .map(|(job_id, job)| (*job_id, job.clone()))
+        .collect::<Vec<(u64, Job)>>();
+    let old_allocations = jobs
+        .iter()
+        .map(|(job_id, job)| {
+            job.graph
+                .node_indices()
+                .map(|x| cluster.allocation(*job_id, x.index() as u32))
+                .collect::<Vec<Option<usize>>>()
+        })
+        .collect::<Vec<Vec<Option<usize>>>>();
+    let old_node_types = cluster
+        .nodes()
+        .iter()
+        .map(|x| x.node_type)
+        .collect::<Vec<usize>>();
+
+    // re-allocate all the jobs, with stable sorting so that ties are
+    // broken by order of arrival
+    let mut order_jobs = (0..jobs.len()).collect::<Vec<usize>>();
+    match order {
+        RepackingOrder::Arrival | RepackingOrder::TaskCpu => {}
+        RepackingOrder::JobCpu => {
+            order_jobs.sort_by_key(|x| std::cmp::Reverse(jobs[*x].1.total_cpu()));
+        }
+        RepackingOrder::JobState => {
+            order_jobs.sort_by_key(|x| std::cmp::Reverse(jobs[*x].1.total_state_size()));
+        }
+        RepackingOrder::RemainingLifetime => {
+            let remaining_lifetimes = jobs
+                .iter()
+                .map(|(job_id, _job)| cluster.remaining_lifetime(*job_id).unwrap_or(0.0))
+                .collect::<Vec<f64>>();
+            order_jobs.sort_by(|a, b| remaining_lifetimes[*b].total_cmp(&remaining_lifetimes[*a]));
+        }
+    }
+    cluster.clear_nodes();
+    match order {
+        RepackingOrder::TaskCpu => {
+            let old_allocations = &old_allocations;
+            let mut tasks = jobs
+                .iter()
+                .enumerate()
+                .flat_map(|(i, (_job_id, job))| {
+                    job.graph
+                        .node_references()
+                        .filter(move |(task_id, _weight)| {
+                            old_allocations[i][task_id.index()].is_some()
+                        })
+                        .map(move |(task_id, weight)| {
+                            (i, task_id.index() as u32, weight.cpu_request)
+                        })
+                })
+                .collect::<Vec<(usize, u32, usize)>>();
+            tasks.sort_by_key(|(_i, _task_id, cpu)| std::cmp::Reverse(*cpu));
+            for (i, task_id, _cpu) in tasks {
+                let (job_id, job) = &jobs[i];
+                policy.allocate_tasks(cluster, *job_id, job, &[task_id]);
+            }
+        }
+        _ => {
+            for i in order_jobs {
+                let (job_id, job) = &jobs[i];
+                let tasks = (0..job.graph.node_count() as u32)
+                    .filter(|x| old_allocations[i][*x as usize].is_some())
+                    .collect::<Vec<u32>>();
+                if tasks.len() == job.graph.node_count() {
+                    policy.allocate(cluster, *job_id, job);
+                } else {
+                    policy.allocate_tasks(cluster, *job_id, job, &tasks);
+                }
+            }
+        }
+    }
+
+    // match the new nodes to the old ones of the same type, with weights
+    // equal to the state size of the tasks they have in common
+    let mut weights = vec![];
+    for ((job_id, job), old_nodes) in jobs.iter().zip(old_allocations.iter()) {
+        for (task_id, weight) in job.graph.node_references() {
+            let Some(old_node) = old_nodes[task_id.index()] else {
+                continue;
+            };
+            let new_node = cluster.allocation(*job_id, task_id.index() as u32).unwrap();
+            if old_node_types[old_node] == cluster.nodes()[new_node].node_type {
+                weights.push((new_node, old_node, weight.state_size as u64));
+            }
+        }
+    }
+    let matching = crate::assignment::max_weight_matching(
+        cluster.nodes().len(),
+        old_node_types.len(),
+        &weights,
+    );
+
+    // the new nodes not matched reuse the IDs of old nodes of the same type
+    // not matched, if any, otherwise they are assigned new IDs
+    let mut node_types = old_node_types.clone();
+    let mut unused = vec![true; old_node_types.len()];
+    for old_node in matching.iter().flatten() {
+        unused[*old_node] = false;
+    }
+    let mut new_ids = vec![];
+    for (new_node, old_node) in matching.iter().enumerate() {
+        let node_type = cluster.nodes()[new_node].node_type;
+        let new_id = match old_node {
+            Some(old_node) => *old_node,
+            None => match (0..unused.len()).find(|x| unused[*x] && node_types[*x] == node_type) {
+                Some(old_node) => {
+                    unused[old_node] = false;
+                    old_node
+                }
+                None => {
+                    node_types.push(node_type);
+                    unused.push(false);
+                    node_types.len() - 1
+                }
+            },
+        };
+        new_ids.push(new_id);
+    }
+    cluster.renumber_nodes(&new_ids, &node_types);
+
+    // count the migrations
+    let mut migration_traffic = 0;
+    let mut num_migrations = 0;
+    for ((job_id, job), old_nodes) in jobs.iter().zip(old_allocations.iter()) {
+        for (task_id, weight) in job.graph.node_references() {
+            let Some(old_node) = old_nodes[task_id.index()] else {
+                continue;
+            };
+            let new_node = cluster.allocation(*job_id, task_id.index() as u32).unwrap();
+            if old_node != new_node {
+                num_migrations += 1;
+                migration_traffic += weight.state_size;
+            }
+        }
+    }
+    (migration_traffic, num_migrations)
+}
+
+/// Empty the active nodes one by one, starting from those with the smallest
+/// state, by migrating all their tasks to the other active nodes, until
+/// the migration budget is exhausted.
+///
+/// Tasks are migrated only if all those of a node can be moved, since
+/// otherwise no node is freed. Each task is moved to the active node that
+/// leaves the smallest residual CPU, considering the tasks with the largest
+/// CPU request first. A node that has received tasks is not emptied.
+///
+/// Return the traffic due to the migration of task state and
+/// the number of migrations.
+pub fn incremental(cluster: &mut Cluster, budget: &MigrationBudget) -> (usize, usize) {
+    empty_nodes(cluster, budget, |_cluster, _source, _tasks| true)
+}
+
+/// Same as [`incremental`], but the tasks of a node are migrated only if
+/// the energy expected to be saved exceeds that consumed to transfer
+/// their state, where the energy saved is given by:
+/// - the idle power of the node released, until the last of its jobs
+///   would have ended;
+/// - the cross-node traffic of the jobs migrated, at the invocation
+///   rate, until each job ends.
+///
+/// Return the traffic due to the migration of task state and
+/// the number of migrations.
+pub fn cost_benefit(
+    cluster: &mut Cluster,
+    budget: &MigrationBudget,
+    cost: &MigrationCost,
+) -> (usize, usize) {
+    empty_nodes(cluster, budget, |cluster, source, tasks| {
+        let network_energy =
+            |traffic: f64| crate::energy::network_energy(traffic, cost.network_energy);
+
+        // the node would have remained busy until the end of its last job
+        let mut job_ids = tasks
+            .iter()
+            .map(|(job_id, _)| *job_id)
+            .collect::<Vec<u64>>();
+        job_ids.sort();
+        job_ids.dedup();
+        let node_lifetime = job_ids
+            .iter()
+            .map(|job_id| cost.remaining_lifetime(cluster, *job_id))
+            .fold(0.0, f64::max);
+        let node_type = &cluster.node_types()[cluster.nodes()[source].node_type];
+        let mut saving = node_type.idle_power * node_lifetime;
+
+        // compare the cross-node traffic of the jobs before and after,
+        // among their tasks allocated, which are not all with batch execution
+        for job_id in job_ids {
+            let job = cluster.job(job_id).unwrap();
+            let after = job
+                .graph
+                .node_indices()
+                .map(|x| cluster.allocation(job_id, x.index() as u32))
+                .collect::<Vec<Option<usize>>>();
+            let mut before = after.clone();
+            for (_job_id, task_id) in tasks.iter().filter(|(x, _)| *x == job_id) {
+                before[*task_id as usize] = Some(source);
+            }
+            let traffic = crate::partition::partial_cut_size(job, &before) as f64
+                - crate::partition::partial_cut_size(job, &after) as f64;
+            saving += network_energy(
+                traffic * cost.invocation_rate * cost.remaining_lifetime(cluster, job_id),
+            );
+        }
+
+        let state_size = tasks
+            .iter()
+            .map(|(job_id, task_id)| {
+                cluster
+                    .job(*job_id)
+                    .unwrap()
+                    .graph
+                    .node_weight((*task_id).into())
+                    .unwrap()
+                    .state_size
+            })
+            .sum::<usize>();
+        saving > network_energy(state_size as f64)
+    })
+}
+
+/// Empty the active nodes as described in [`incremental`], where the tasks
+/// of a node are migrated only if `accept` returns true: this is called
+/// after the migration with the source node and the tasks migrated,
+/// otherwise the migration is reverted.
+fn empty_nodes<F>(cluster: &mut Cluster, budget: &MigrationBudget, mut accept: F) -> (usize, usize)
+where
+    F: FnMut(&Cluster, usize, &[(u64, u32)]) -> bool,
+{
+    let num_nodes = cluster.nodes().len();
+    let mut used = (0..num_nodes)
+        .map(|node_id| cluster.capacity_used(node_id))
+        .collect::<Vec<Resources>>();
+
+    // the state to be migrated is the memory used on the node
+    let mut sources = (0..num_nodes)
+        .filter(|node_id| cluster.nodes()[*node_id].is_active())
+        .collect::<Vec<usize>>();
+    sources.sort_by_key(|node_id| (used[*node_id].memory, used[*node_id].cpu, *node_id));
+
+    let mut receiving = vec![false; num_nodes];
+    let mut migration_traffic = 0;
+    let mut num_migrations = 0;
+    for source in sources {
+        if receiving[source]
+            || !budget.allows(
+                num_migrations + cluster.nodes()[source].jobs.len(),
+                migration_traffic + used[source].memory,
+            )
+        {
+            continue;
+        }
+
+        let mut tasks = cluster.nodes()[source]
+            .jobs
+            .iter()
+            .map(|(job_id, task_id)| {
+                let demand = cluster
+                    .job(*job_id)
+                    .unwrap()
+                    .graph
+                    .node_weight((*task_id).into())
+                    .unwrap()
+                    .demand();
+                (*job_id, *task_id, demand)
+            })
+            .collect::<Vec<(u64, u32, Resources)>>();
+        tasks.sort_by_key(|(job_id, task_id, demand)| {
+            (std::cmp::Reverse(demand.cpu), *job_id, *task_id)
+        });
+
+        // find a destination for every task, without modifying the cluster
+        let mut new_used = used.clone();
+        let mut destinations = vec![];
+        for (_job_id, _task_id, demand) in &tasks {
+            match (0..num_nodes)
+                .filter(|node_id| *node_id != source && cluster.nodes()[*node_id].is_active())
+                .filter_map(|node_id| {
+                    let capacity = cluster.node_capacity(node_id);
+                    let node_used = new_used[node_id] + *demand;
+                    match node_used.fits_into(&capacity) {
+                        true => Some((capacity.cpu - node_used.cpu, node_id)),
+                        false => None,
+                    }
+                })
+                .min()
+            {
+                Some((_residual, node_id)) => {
+                    new_used[node_id] += *demand;
+                    destinations.push(node_id);
+                }
+                None => break,
+            }
+        }
+        if destinations.len() < tasks.len() {
+            continue;
+        }
+
+        // migrate the tasks, unless not accepted
+        for ((job_id, task_id, _demand), destination) in tasks.iter().zip(destinations.iter()) {
+            cluster.del_task(*job_id, *task_id);
+            cluster.add_task(*job_id, *task_id, *destination);
+        }
+        let migrated = tasks
+            .iter()
+            .map(|(job_id, task_id, _demand)| (*job_id, *task_id))
+            .collect::<Vec<(u64, u32)>>();
+        if !accept(cluster, source, &migrated) {
+            for (job_id, task_id) in migrated {
+                cluster.del_task(job_id, task_id);
+                cluster.add_task(job_id, task_id, source);
+            }
+            continue;
+        }
+        for ((_job_id, _task_id, demand), destination) in tasks.iter().zip(destinations) {
+            receiving[destination] = true;
+            num_migrations += 1;
+            migration_traffic += demand.memory;
+        }
+        new_used[source] = Resources::default();
+        used = new_used;
+    }
+    (migration_traffic, num_migrations)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cluster::{NodeType, Resources};
+    use crate::job::{Edge, Job, Vertex};
+
+    fn make_cluster(jobs: Vec<(usize, usize)>) -> Cluster {
+        let mut cluster = Cluster::new(vec![NodeType::new(
+            "default",
+            Resources::new(1000, 1000000),
+            50.0,
+            100.0,
+            1.0,
+        )]);
+        for (job_id, (cpu, state)) in jobs.into_iter().enumerate() {
+            cluster.insert_job(
+                job_id as u64,
+                Job::new(vec![Vertex::new(cpu, state)], vec![]),
+            );
+        }
+        cluster
+    }
+
+    #[test]
+    fn test_full_repack_same_nodes() {
+        // the jobs are already allocated optimally in swapped order
+        // with respect to the one of creation of the nodes
+        for seed in 0..10 {
+            let mut cluster = make_cluster(vec![(600, 10), (600, 20), (600, 30)]);
+            for job_id in 0..3 {
+                let node_id = cluster.add_node(0);
+                cluster.add_task(2 - job_id, 0, node_id);
+            }
+            let mut policy = crate::policy::StatefulBestFit::new(seed);
+            assert_eq!(
+                (0, 0),
+                full_repack(&mut policy, &mut cluster, RepackingOrder::Arrival)
+            );
+            assert_eq!(Some(0), cluster.allocation(2, 0));
+            assert_eq!(Some(2), cluster.allocation(0, 0));
+        }
+    }
+
+    #[test]
+    fn test_full_repack_consolidation() {
+        // three jobs on three nodes can be consolidated into one node:
+        // the job with the largest state must remain in its node
+        for seed in 0..10 {
+            let mut cluster = make_cluster(vec![(300, 10), (300, 30), (300, 20)]);
+            for job_id in 0..3 {
+                let node_id = cluster.add_node(0);
+                cluster.add_task(job_id, 0, node_id);
+            }
+            let mut policy = crate::policy::StatefulBestFit::new(seed);
+            assert_eq!(
+                (30, 2),
+                full_repack(&mut policy, &mut cluster, RepackingOrder::Arrival)
+            );
+            for job_id in 0..3 {
+                assert_eq!(Some(1), cluster.allocation(job_id, 0));
+            }
+            assert_eq!(1, cluster.node_loads().len());
+            assert_eq!(3, cluster.nodes().len());
+        }
+    }
+
+    #[test]
+    fn test_full_repack_order() {
+        // first-fit decreasing packs the four jobs into two nodes,
+        // while the order of arrival requires three nodes
+        for (order, expected) in [
+            (RepackingOrder::Arrival, 3),
+            (RepackingOrder::TaskCpu, 2),
+            (RepackingOrder::JobCpu, 2),
+            (RepackingOrder::JobState, 2),
+            (RepackingOrder::RemainingLifetime, 2),
+        ] {
+            let mut cluster = make_cluster(vec![(300, 10), (300, 20), (700, 30), (700, 40)]);
+            for job_id in 0..4 {
+                let node_id = cluster.add_node(0);
+                cluster.add_task(job_id, 0, node_id);
+                cluster.set_job_end(job_id, job_id as f64);
+            }
+            let mut policy = crate::policy::StatefulBestFit::new(42);
+            full_repack(&mut policy, &mut cluster, order);
+            assert_eq!(expected, cluster.node_loads().len(), "{}", order);
+        }
+    }
+
+    #[test]
+    fn test_incremental() {
+        let budgets = [
+            (None, None, (30, 2)),
+            (Some(1), None, (10, 1)),
+            (None, Some(15), (10, 1)),
+            (None, Some(5), (0, 0)),
+        ];
+        for (tasks, state, expected) in budgets {
+            let mut cluster = make_cluster(vec![(300, 10), (300, 30), (300, 20)]);
+            for job_id in 0..3 {
+                let node_id = cluster.add_node(0);
+                cluster.add_task(job_id, 0, node_id);
+            }
+            let budget = MigrationBudget { tasks, state };
+            assert_eq!(expected, incremental(&mut cluster, &budget));
+            assert_eq!(Some(1), cluster.allocation(1, 0));
+            assert_eq!(3 - expected.1, cluster.node_loads().len());
+        }
+    }
+
+    #[test]
+    fn test_incremental_whole_nodes() {
+        // the node with the smallest state is emptied, then the other
+        // one cannot be emptied because its task fits nowhere
+        let mut cluster = make_cluster(vec![(600, 10), (300, 30), (300, 20)]);
+        for job_id in 0..3 {
+            let node_id = cluster.add_node(0);
+            cluster.add_task(job_id, 0, node_id);
+        }
+        assert_eq!(
+            (10, 1),
+            incremental(&mut cluster, &MigrationBudget::default())
+        );
+        assert_eq!(Some(1), cluster.allocation(0, 0));
+        assert_eq!(Some(2), cluster.allocation(2, 0));
+    }
+
+    #[test]
+    fn test_fragmentation_index() {
+        let mut cluster = make_cluster(vec![(300, 10), (300, 30), (300, 20)]);
+        assert_eq!(1.0, fragmentation_index(&cluster, cluster.type_loads()));
+        for job_id in 0..3 {
+            let node_id = cluster.add_node(0);
+            cluster.add_task(job_id, 0, node_id);
+        }
+        assert_eq!(3.0, fragmentation_index(&cluster, cluster.type_loads()));
+        incremental(&mut cluster, &MigrationBudget::default());
+        assert_eq!(1.0, fragmentation_index(&cluster, cluster.type_loads()));
+
+        // the memory demand requires two nodes
+        let mut cluster = make_cluster(vec![(100, 600000), (100, 600000)]);
+        for job_id in 0..2 {
+            let node_id = cluster.add_node(0);
+            cluster.add_task(job_id, 0, node_id);
+        }
+        assert_eq!(1.0, fragmentation_index(&cluster, cluster.type_loads()));
+
+        // the nodes of each type are compared with those of the same type
+        let mut cluster = Cluster::new(vec![
+            NodeType::new("small", Resources::new(200, 1000000), 50.0, 100.0, 1.0),
+            NodeType::new("large", Resources::new(1000, 1000000), 50.0, 100.0, 1.0),
+        ]);
+        for job_id in 0..3 {
+            cluster.insert_job(job_id, Job::new(vec![Vertex::new(200, 10)], vec![]));
+            let node_id = cluster.add_node(0);
+            cluster.add_task(job_id, 0, node_id);
+        }
+        assert_eq!(1.0, fragmentation_index(&cluster, cluster.type_loads()));
+        cluster.insert_job(3, Job::new(vec![Vertex::new(100, 10)], vec![]));
+        cluster.insert_job(4, Job::new(vec![Vertex::new(100, 10)], vec![]));
+        for job_id in 3..5 {
+            let node_id = cluster.add_node(1);
+            cluster.add_task(job_id, 0, node_id);
+        }
+        assert_eq!(
+            5.0 / 4.0,
+            fragmentation_index(&cluster, cluster.type_loads())
+        );
+    }
+
+    #[test]
+    fn test_cost_benefit_lifetime() {
+        let cost = MigrationCost {
+            invocation_rate: 1.0,
+            network_energy: 0.4,
+            job_lifetime: 100.0,
+            remaining_lifetime: RemainingLifetime::Oracle,
+        };
+        // the cost of migration is 40000 J, while the idle power is 50 W
+        for (remaining_lifetime, end_time, expected) in [
+            (RemainingLifetime::Oracle, 100.0, (0, 0)),
+            (RemainingLifetime::Oracle, 1000.0, (100000, 1)),
+            (RemainingLifetime::Estimated, 1000.0, (0, 0)),
+        ] {
+            let mut cluster = make_cluster(vec![(300, 100000), (300, 200000)]);
+            for job_id in 0..2 {
+                let node_id = cluster.add_node(0);
+                cluster.add_task(job_id, 0, node_id);
+                cluster.set_job_end(job_id, end_time);
+            }
+            let cost = MigrationCost {
+                remaining_lifetime,
+                ..cost
+            };
+            assert_eq!(
+                expected,
+                cost_benefit(&mut cluster, &MigrationBudget::default(), &cost)
+            );
+            assert_eq!(2 - expected.1, cluster.node_loads().len());
+            assert_eq!(Some(1), cluster.allocation(1, 0));
+        }
+    }
+
+    #[test]
+    fn test_cost_benefit_traffic() {
+        // the migration of a task with 1000 MB of state costs 400 J and
+        // saves 50 J of idle power, which is not enough unless the
+        // traffic between the two tasks is also saved
+        for (invocation_rate, expected) in [(0.0, (0, 0)), (1.0, (1000, 1))] {
+            let mut cluster = Cluster::new(vec![NodeType::new(
+                "default",
+                Resources::new(1000, 1000000),
+                50.0,
+                100.0,
+                1.0,
+            )]);
+            cluster.insert_job(
+                0,
+                Job::new(
+                    vec![Vertex::new(300, 1000), Vertex::new(400, 2000)],
+                    vec![(0, 1, Edge::new(1000))],
+                ),
+            );
+            for task_id in 0..2 {
+                let node_id = cluster.add_node(0);
+                cluster.add_task(0, task_id, node_id);
+            }
+            let cost = MigrationCost {
+                invocation_rate,
+                network_energy: 0.4,
+                job_lifetime: 1.0,
+                remaining_lifetime: RemainingLifetime::Estimated,
+            };
+            assert_eq!(
+                expected,
+                cost_benefit(&mut cluster, &MigrationBudget::default(), &cost)
+            );
+            assert_eq!(2 - expected.1, cluster.node_loads().len());
+        }
+    }
+}
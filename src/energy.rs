@@ -0,0 +1,66 @@
+use crate::cluster::{NodeType, Resources, TypeLoad};
+
+/// Number of J in one kWh.
+const JOULES_PER_KWH: f64 = 3.6e6;
+
+/// Return the power consumed by a node of the given type, in W, which
+/// grows linearly with the CPU load from the idle to the peak power.
+pub fn node_power(node_type: &NodeType, used: &Resources) -> f64 {
+    let load = f64::min(1.0, used.cpu as f64 / node_type.capacity.cpu as f64);
+    node_type.idle_power + (node_type.peak_power - node_type.idle_power) * load
+}
+
+/// Return the power consumed by the busy nodes of the given type, in W,
+/// assuming that none of them exceeds its CPU capacity.
+pub fn type_power(node_type: &NodeType, load: &TypeLoad) -> f64 {
+    node_type.idle_power * load.busy_nodes as f64
+        + (node_type.peak_power - node_type.idle_power) * load.used.cpu as f64
+            / node_type.capacity.cpu as f64
+}
+
+/// Return the energy consumed to transfer data between nodes, in J.
+///
+/// Parameters:
+/// - `traffic`: the data transferred, in MB
+/// - `energy_per_byte`: the energy to transfer one byte, in uJ
+pub fn network_energy(traffic: f64, energy_per_byte: f64) -> f64 {
+    traffic * 1e6 * energy_per_byte * 1e-6
+}
+
+/// Convert an energy from J to kWh.
+pub fn to_kwh(energy: f64) -> f64 {
+    energy / JOULES_PER_KWH
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_energy_node_power() {
+        let node_type = NodeType::new("default", Resources::new(1000, 1000), 50.0, 100.0, 1.0);
+        assert_eq!(50.0, node_power(&node_type, &Resources::new(0, 1000)));
+        assert_eq!(75.0, node_power(&node_type, &Resources::new(500, 0)));
+        assert_eq!(100.0, node_power(&node_type, &Resources::new(1000, 0)));
+    }
+
+    #[test]
+    fn test_energy_type_power() {
+        let node_type = NodeType::new("default", Resources::new(1000, 1000), 50.0, 100.0, 1.0);
+        let load = TypeLoad {
+            busy_nodes: 2,
+            used: Resources::new(1500, 0),
+        };
+        assert_eq!(
+            node_power(&node_type, &Resources::new(1000, 0))
+                + node_power(&node_type, &Resources::new(500, 0)),
+            type_power(&node_type, &load)
+        );
+    }
+
+    #[test]
+    fn test_energy_conversions() {
+        assert_eq!(2.0, network_energy(5.0, 0.4));
+        assert_eq!(1.0, to_kwh(3600.0 * 1000.0));
+    }
+}
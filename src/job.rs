@@ -2,7 +2,7 @@ use crate::rv_histo;
 use rand::seq::SliceRandom;
 use rand::SeedableRng;
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, serde::Deserialize)]
 pub struct Vertex {
     /// CPU requested to execute this task, every 100 unit means 1 core
     pub cpu_request: usize,
@@ -17,6 +17,12 @@ impl Vertex {
             state_size,
         }
     }
+
+    /// Resources needed on the node hosting this task: CPU and
+    /// memory to keep its internal state.
+    pub fn demand(&self) -> crate::cluster::Resources {
+        crate::cluster::Resources::new(self.cpu_request, self.state_size)
+    }
 }
 
 impl std::fmt::Display for Vertex {
@@ -76,8 +82,176 @@ impl Job {
         self.graph.edge_weights().map(|x| x.arg_size).sum()
     }
 
+    /// Return the time at which each task finishes, relative to the start
+    /// of the job, given the durations of the tasks, if every task starts
+    /// as soon as all the tasks it depends on have finished.
+    ///
+    /// Panics if the graph of the job has cycles.
+    pub fn finish_times(&self, durations: &[f64]) -> Vec<f64> {
+        assert_eq!(self.graph.node_count(), durations.len());
+        let order = petgraph::algo::toposort(&self.graph, None)
+            .unwrap_or_else(|_| panic!("the tasks of a job cannot have cyclic dependencies"));
+        let mut finish_times = vec![0.0; durations.len()];
+        for task in order {
+            let start = self
+                .graph
+                .neighbors_directed(task, petgraph::Direction::Incoming)
+                .map(|x| finish_times[x.index()])
+                .fold(0.0, f64::max);
+            finish_times[task.index()] = start + durations[task.index()];
+        }
+        finish_times
+    }
+
+    /// Read a job from a JSON description, e.g.:
+    ///
+    /// ```json
+    /// {
+    ///   "vertices": [{"cpu_request": 100, "state_size": 10}, {"cpu_request": 200, "state_size": 20}],
+    ///   "edges": [{"source": 0, "target": 1, "arg_size": 5}]
+    /// }
+    /// ```
+    ///
+    /// where the edges refer to the vertices by their position.
+    pub fn from_json(description: &str) -> anyhow::Result<Self> {
+        let description = serde_json::from_str::<JobDescription>(description)?;
+        let num_vertices = description.vertices.len() as u32;
+        let mut edges = vec![];
+        for edge in description.edges {
+            anyhow::ensure!(
+                edge.source < num_vertices && edge.target < num_vertices,
+                "invalid edge {} -> {} with {} vertices",
+                edge.source,
+                edge.target,
+                num_vertices
+            );
+            edges.push((edge.source, edge.target, Edge::new(edge.arg_size)));
+        }
+        Self::new(description.vertices, edges).acyclic()
+    }
+
+    /// Read a job from a Graphviz DOT description, in the format
+    /// produced by [`Job::to_dot`].
+    pub fn from_dot(description: &str) -> anyhow::Result<Self> {
+        // return the values in a label like "(key1 = value1, key2 = value2)"
+        let parse_label = |line: &str, keys: &[&str]| -> anyhow::Result<Vec<usize>> {
+            let label = match (line.find('"'), line.rfind('"')) {
+                (Some(begin), Some(end)) if begin < end => &line[begin + 1..end],
+                _ => anyhow::bail!("missing label: {}", line),
+            };
+            let mut values = std::collections::HashMap::new();
+            for field in label.trim_matches(|x| x == '(' || x == ')').split(',') {
+                if let Some((key, value)) = field.split_once('=') {
+                    values.insert(key.trim(), value.trim());
+                }
+            }
+            keys.iter()
+                .map(|key| match values.get(key) {
+                    Some(value) => Ok(value.parse::<usize>()?),
+                    None => anyhow::bail!("missing {} in label: {}", key, line),
+                })
+                .collect()
+        };
+
+        let mut vertices = vec![];
+        let mut edges = vec![];
+        for line in description.lines() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with("digraph") || line == "}" {
+                continue;
+            }
+            let (ids, _) = line
+                .split_once('[')
+                .ok_or_else(|| anyhow::anyhow!("invalid line: {}", line))?;
+            match ids.split_once("->") {
+                Some((u, v)) => {
+                    let values = parse_label(line, &["arg"])?;
+                    edges.push((
+                        u.trim().parse::<u32>()?,
+                        v.trim().parse::<u32>()?,
+                        values[0],
+                    ));
+                }
+                None => {
+                    anyhow::ensure!(
+                        ids.trim().parse::<usize>()? == vertices.len(),
+                        "vertices not in order: {}",
+                        line
+                    );
+                    let values = parse_label(line, &["cpu", "state"])?;
+                    vertices.push(Vertex::new(values[0], values[1]));
+                }
+            }
+        }
+        let num_vertices = vertices.len() as u32;
+        let mut job_edges = vec![];
+        for (u, v, arg_size) in edges {
+            anyhow::ensure!(
+                u < num_vertices && v < num_vertices,
+                "invalid edge {} -> {} with {} vertices",
+                u,
+                v,
+                num_vertices
+            );
+            job_edges.push((u, v, Edge::new(arg_size)));
+        }
+        Self::new(vertices, job_edges).acyclic()
+    }
+
+    /// Return the job if its tasks do not have cyclic dependencies,
+    /// including those of a task on itself.
+    fn acyclic(self) -> anyhow::Result<Self> {
+        anyhow::ensure!(
+            !petgraph::algo::is_cyclic_directed(&self.graph),
+            "cyclic dependencies between tasks"
+        );
+        Ok(self)
+    }
+
+    /// Check that the job can be simulated with the given node types, i.e.,
+    /// that it does not have more than [`crate::cluster::MAX_TASKS_PER_JOB`]
+    /// tasks and that every task fits into at least one node type.
+    pub fn validate(&self, node_types: &[crate::cluster::NodeType]) -> anyhow::Result<()> {
+        anyhow::ensure!(
+            self.graph.node_count() <= crate::cluster::MAX_TASKS_PER_JOB,
+            "too many tasks: {}",
+            self.graph.node_count()
+        );
+        for (task_id, vertex) in self.graph.node_weights().enumerate() {
+            let demand = vertex.demand();
+            anyhow::ensure!(
+                node_types.iter().any(|x| demand.fits_into(&x.capacity)),
+                "task {} with demand {} does not fit into any node type",
+                task_id,
+                demand
+            );
+        }
+        Ok(())
+    }
+
+    /// Read a job from a file, in JSON if its extension is `.json` or
+    /// in Graphviz DOT if it is `.dot` or `.gv`.
+    pub fn from_file(filename: &str) -> anyhow::Result<Self> {
+        let description = std::fs::read_to_string(filename)
+            .map_err(|err| anyhow::anyhow!("could not read job file {}: {}", filename, err))?;
+        let job = match std::path::Path::new(filename)
+            .extension()
+            .and_then(|x| x.to_str())
+        {
+            Some("json") => Self::from_json(&description),
+            Some("dot") | Some("gv") => Self::from_dot(&description),
+            _ => anyhow::bail!("unknown format of job file {}", filename),
+        };
+        job.map_err(|err| anyhow::anyhow!("invalid job file {}: {}", filename, err))
+    }
+
+    /// Return the description of the job in Graphviz DOT format.
+    pub fn to_dot(&self) -> String {
+        format!("{}", petgraph::dot::Dot::new(&self.graph))
+    }
+
     pub fn print_to_dot(&self) {
-        println!("{}", petgraph::dot::Dot::new(&self.graph))
+        println!("{}", self.to_dot())
     }
 }
 
@@ -95,6 +269,55 @@ impl std::fmt::Display for Job {
     }
 }
 
+/// Description of a job in JSON.
+#[derive(serde::Deserialize)]
+struct JobDescription {
+    vertices: Vec<Vertex>,
+    #[serde(default)]
+    edges: Vec<EdgeDescription>,
+}
+
+/// Description of an edge in JSON, with the positions of its vertices.
+#[derive(serde::Deserialize)]
+struct EdgeDescription {
+    source: u32,
+    target: u32,
+    arg_size: usize,
+}
+
+/// How the tasks of a job are executed during its lifetime.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum JobExecution {
+    /// All the tasks are active for the whole lifetime of the job,
+    /// as in a long-running stateful pipeline.
+    Pipeline,
+    /// Each task runs for its own duration, starting as soon as all the
+    /// tasks it depends on have finished, and the job ends with its
+    /// last task, as in a batch DAG.
+    Batch,
+}
+
+impl std::str::FromStr for JobExecution {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> anyhow::Result<Self> {
+        match s {
+            "pipeline" => Ok(Self::Pipeline),
+            "batch" => Ok(Self::Batch),
+            _ => Err(anyhow::anyhow!("unknown job execution: {}", s)),
+        }
+    }
+}
+
+impl std::fmt::Display for JobExecution {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Pipeline => write!(f, "pipeline"),
+            Self::Batch => write!(f, "batch"),
+        }
+    }
+}
+
 pub struct JobFactory {
     /// Number of tasks in this DAG
     num_rv: rv_histo::RvHisto,
@@ -187,8 +410,10 @@ impl JobFactory {
         // assign a level (with 1-based index) to each task
         // - tasks in the critical path form a chain
         // - all other tasks are assigned as siblings of one of the tasks in the critical path
-        let saturate = |x| std::cmp::max(std::cmp::min(x, 20), 1);
-        let mut level = std::collections::HashMap::new();
+        let saturate = |x: u32| x.clamp(1, 20);
+        // levels are iterated in order below, so that edges are drawn
+        // deterministically
+        let mut level = std::collections::BTreeMap::new();
         for i in 0..cpl {
             level.insert(i + 1, vec![i + 1]);
         }
@@ -264,6 +489,7 @@ impl JobFactory {
 }
 
 #[cfg(test)]
+#[allow(clippy::manual_range_contains)]
 mod tests {
     use super::*;
 
@@ -290,6 +516,145 @@ mod tests {
         assert_eq!(100, job.total_arg_size());
     }
 
+    #[test]
+    fn test_job_from_json() -> anyhow::Result<()> {
+        let job = Job::from_json(
+            r#"{
+                "vertices": [
+                    {"cpu_request": 100, "state_size": 10},
+                    {"cpu_request": 200, "state_size": 20},
+                    {"cpu_request": 300, "state_size": 30}
+                ],
+                "edges": [
+                    {"source": 0, "target": 1, "arg_size": 5},
+                    {"source": 1, "target": 2, "arg_size": 7}
+                ]
+            }"#,
+        )?;
+        assert_eq!(3, job.graph.node_count());
+        assert_eq!(600, job.total_cpu());
+        assert_eq!(60, job.total_state_size());
+        assert_eq!(12, job.total_arg_size());
+        assert!(job.graph.contains_edge(1.into(), 2.into()));
+
+        let job = Job::from_json(r#"{"vertices": [{"cpu_request": 100, "state_size": 10}]}"#)?;
+        assert_eq!((1, 0), (job.graph.node_count(), job.graph.edge_count()));
+
+        assert!(Job::from_json(r#"{"vertices": [{"cpu_request": 100}]}"#).is_err());
+
+        // cyclic dependencies, also of a task on itself
+        let vertices = r#""vertices": [
+            {"cpu_request": 100, "state_size": 10},
+            {"cpu_request": 200, "state_size": 20}
+        ]"#;
+        assert!(Job::from_json(&format!(
+            r#"{{{}, "edges": [
+                {{"source": 0, "target": 1, "arg_size": 5}},
+                {{"source": 1, "target": 0, "arg_size": 5}}
+            ]}}"#,
+            vertices
+        ))
+        .is_err());
+        assert!(Job::from_json(&format!(
+            r#"{{{}, "edges": [{{"source": 1, "target": 1, "arg_size": 5}}]}}"#,
+            vertices
+        ))
+        .is_err());
+        assert!(Job::from_json(
+            r#"{"vertices": [], "edges": [{"source": 0, "target": 1, "arg_size": 5}]}"#
+        )
+        .is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_job_from_dot() -> anyhow::Result<()> {
+        let job = Job::new(
+            vec![
+                Vertex::new(100, 1),
+                Vertex::new(200, 2),
+                Vertex::new(300, 3),
+            ],
+            vec![(0, 1, Edge::new(10)), (0, 2, Edge::new(20))],
+        );
+        let parsed = Job::from_dot(&job.to_dot())?;
+        assert_eq!(job.to_dot(), parsed.to_dot());
+
+        assert!(Job::from_dot("digraph {\n    0 [ label = \"(cpu = 100)\" ]\n}").is_err());
+        assert!(
+            Job::from_dot("digraph {\n    1 [ label = \"(cpu = 100, state = 1)\" ]\n}").is_err()
+        );
+        assert!(Job::from_dot("digraph {\n    0 -> 1 [ label = \"(arg = 10)\" ]\n}").is_err());
+        let cyclic = "digraph {
+            0 [ label = \"(cpu = 100, state = 1)\" ]
+            1 [ label = \"(cpu = 200, state = 2)\" ]
+            0 -> 1 [ label = \"(arg = 10)\" ]
+            1 -> 0 [ label = \"(arg = 10)\" ]
+        }";
+        assert!(Job::from_dot(cyclic).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_job_validate() -> anyhow::Result<()> {
+        let node_types = vec![crate::cluster::NodeType::new(
+            "default",
+            crate::cluster::Resources::new(1000, 1000),
+            50.0,
+            100.0,
+            1.0,
+        )];
+        let job = Job::from_dot(
+            "digraph {
+                0 [ label = \"(cpu = 100, state = 1)\" ]
+                1 [ label = \"(cpu = 2000, state = 2)\" ]
+                0 -> 1 [ label = \"(arg = 10)\" ]
+            }",
+        )?;
+        assert!(job.validate(&node_types).is_err());
+
+        let job = Job::new(vec![Vertex::new(100, 1); 1000], vec![]);
+        assert!(job.validate(&node_types).is_ok());
+        let job = Job::new(vec![Vertex::new(100, 1); 1001], vec![]);
+        assert!(job.validate(&node_types).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_job_finish_times() {
+        let job = Job::new(
+            vec![
+                Vertex::new(100, 1),
+                Vertex::new(200, 2),
+                Vertex::new(300, 3),
+                Vertex::new(400, 4),
+                Vertex::new(500, 5),
+            ],
+            vec![
+                (0, 1, Edge::new(10)),
+                (0, 2, Edge::new(20)),
+                (1, 3, Edge::new(30)),
+                (2, 3, Edge::new(40)),
+            ],
+        );
+        assert_eq!(
+            vec![1.0, 3.0, 6.0, 10.0, 5.0],
+            job.finish_times(&[1.0, 2.0, 5.0, 4.0, 5.0])
+        );
+        assert_eq!(vec![0.0; 5], job.finish_times(&[0.0; 5]));
+    }
+
+    #[test]
+    fn test_job_execution_from_str() {
+        for execution in [JobExecution::Pipeline, JobExecution::Batch] {
+            assert_eq!(
+                execution,
+                execution.to_string().parse::<JobExecution>().unwrap()
+            );
+        }
+        assert!("dag".parse::<JobExecution>().is_err());
+    }
+
     #[test]
     fn test_job_factory() -> anyhow::Result<()> {
         let mut jf = JobFactory::new(42, 10000.0, 100.0)?;
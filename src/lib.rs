@@ -1,3 +1,15 @@
+pub mod alibaba;
+pub mod arrival;
+pub mod assignment;
+pub mod catalog;
+pub mod cluster;
+pub mod confidence;
+pub mod defragmentation;
+pub mod energy;
 pub mod job;
+pub mod partition;
+pub mod policy;
 pub mod rv_histo;
 pub mod simulation;
+pub mod trace;
+pub mod warm_up;
@@ -0,0 +1,381 @@
+use petgraph::visit::EdgeRef;
+
+use crate::cluster::Resources;
+
+/// Maximum number of Fiduccia-Mattheyses refinement passes.
+const MAX_PASSES: usize = 10;
+
+/// Partition the tasks of a job into groups whose total demand does not
+/// exceed `capacity` in any dimension, trying to minimize the total size of the arguments
+/// exchanged between tasks in different groups.
+///
+/// The initial partition is found greedily by visiting the tasks in
+/// topological order and adding each of them to the group with which it
+/// exchanges the most data, among those with sufficient residual capacity.
+/// The partition is then refined with Fiduccia-Mattheyses passes, where
+/// every task is moved once to the best other group, or swapped with a
+/// task in another group as in Kernighan-Lin, even if this worsens the
+/// cut temporarily, and only the best prefix of moves is retained.
+/// The gains of the moves are kept in buckets sorted by gain and updated
+/// incrementally from the adjacency lists of the tasks moved.
+///
+/// Return the group of each task, with group indices in 0..number of groups,
+/// or an error if a task does not fit into `capacity`.
+pub fn partition(job: &crate::job::Job, capacity: Resources) -> anyhow::Result<Vec<usize>> {
+    let num_tasks = job.graph.node_count();
+    let demand = job
+        .graph
+        .node_weights()
+        .map(|x| x.demand())
+        .collect::<Vec<Resources>>();
+    anyhow::ensure!(
+        demand.iter().all(|x| x.fits_into(&capacity)),
+        "task demand exceeding the capacity {}",
+        capacity
+    );
+    let fits = |load: Resources| load.fits_into(&capacity);
+
+    // undirected adjacency lists, weighted by the argument size
+    let mut adjacency = vec![vec![]; num_tasks];
+    for edge in job.graph.edge_references() {
+        let u = edge.source().index();
+        let v = edge.target().index();
+        if u != v {
+            adjacency[u].push((v, edge.weight().arg_size));
+            adjacency[v].push((u, edge.weight().arg_size));
+        }
+    }
+
+    // greedy initial partition
+    let order = match petgraph::algo::toposort(&job.graph, None) {
+        Ok(order) => order.iter().map(|x| x.index()).collect(),
+        Err(_) => (0..num_tasks).collect::<Vec<usize>>(),
+    };
+    let mut groups = vec![usize::MAX; num_tasks];
+    let mut loads: Vec<Resources> = vec![];
+    for u in order {
+        let mut connection = vec![0; loads.len()];
+        for (v, weight) in &adjacency[u] {
+            if groups[*v] != usize::MAX {
+                connection[groups[*v]] += weight;
+            }
+        }
+        let best = (0..loads.len())
+            .filter(|g| fits(loads[*g] + demand[u]))
+            .max_by(|a, b| connection[*a].cmp(&connection[*b]).then(b.cmp(a)));
+        let group = match best {
+            Some(group) => group,
+            None => {
+                loads.push(Resources::default());
+                loads.len() - 1
+            }
+        };
+        groups[u] = group;
+        loads[group] += demand[u];
+    }
+
+    // Fiduccia-Mattheyses refinement
+    let num_groups = loads.len();
+    for _ in 0..MAX_PASSES {
+        // data exchanged by each task with every group, updated
+        // incrementally as the tasks are moved
+        let mut connection = vec![vec![0_i64; num_groups]; num_tasks];
+        for (u, neighbors) in adjacency.iter().enumerate() {
+            for (v, weight) in neighbors {
+                connection[u][groups[*v]] += *weight as i64;
+            }
+        }
+        let mut buckets = GainBuckets::new(num_groups);
+        for u in 0..num_tasks {
+            buckets.insert(u, groups[u], &connection[u]);
+        }
+
+        let mut locked = vec![false; num_tasks];
+        let mut moves = vec![];
+        let mut cumulative_gain = 0_i64;
+        let mut best_gain = 0_i64;
+        let mut best_prefix = 0;
+        loop {
+            // find the best feasible move among the unlocked tasks, either
+            // moving a single task to another group or swapping two tasks
+            // in different groups, which is the only option when all
+            // the groups are (almost) full; the tasks are visited in
+            // decreasing order of gain, until none can beat the best move
+            let mut best_move: Option<(i64, usize, Option<usize>, usize)> = None;
+            for a in 0..num_groups {
+                for b in (0..num_groups).filter(|b| *b != a) {
+                    for (gain, u) in buckets.iter(a, b) {
+                        if best_move.is_some_and(|best| gain <= best.0) {
+                            break;
+                        }
+                        if fits(loads[b] + demand[u]) {
+                            best_move = Some((gain, u, None, b));
+                            break;
+                        }
+                    }
+                }
+            }
+            for a in 0..num_groups {
+                for b in a + 1..num_groups {
+                    let Some((max_gain_v, _)) = buckets.iter(b, a).next() else {
+                        continue;
+                    };
+                    for (gain_u, u) in buckets.iter(a, b) {
+                        if best_move.is_some_and(|best| gain_u + max_gain_v <= best.0) {
+                            break;
+                        }
+                        for (gain_v, v) in buckets.iter(b, a) {
+                            if best_move.is_some_and(|best| gain_u + gain_v <= best.0) {
+                                break;
+                            }
+                            if fits(loads[a] - demand[u] + demand[v])
+                                && fits(loads[b] - demand[v] + demand[u])
+                            {
+                                let weight = adjacency[u]
+                                    .iter()
+                                    .filter(|(x, _)| *x == v)
+                                    .map(|(_, weight)| *weight as i64)
+                                    .sum::<i64>();
+                                let gain = gain_u + gain_v - 2 * weight;
+                                if best_move.is_none_or(|best| gain > best.0) {
+                                    best_move = Some((gain, u, Some(v), b));
+                                }
+                            }
+                        }
+                    }
+                }
+            }
+            let (gain, u, v, group) = match best_move {
+                Some(best_move) => best_move,
+                None => break,
+            };
+
+            // apply it tentatively, locking the tasks moved and updating
+            // the gains of their unlocked neighbors
+            let old_group = groups[u];
+            let mut apply = |x: usize, group: usize| {
+                buckets.remove(x, groups[x], &connection[x]);
+                moves.push((x, groups[x]));
+                loads[groups[x]] -= demand[x];
+                loads[group] += demand[x];
+                for (y, weight) in &adjacency[x] {
+                    if !locked[*y] {
+                        buckets.remove(*y, groups[*y], &connection[*y]);
+                        connection[*y][groups[x]] -= *weight as i64;
+                        connection[*y][group] += *weight as i64;
+                        buckets.insert(*y, groups[*y], &connection[*y]);
+                    }
+                }
+                groups[x] = group;
+                locked[x] = true;
+            };
+            if let Some(v) = v {
+                apply(v, old_group);
+            }
+            apply(u, group);
+            cumulative_gain += gain;
+            if cumulative_gain > best_gain {
+                best_gain = cumulative_gain;
+                best_prefix = moves.len();
+            }
+        }
+
+        // roll back the moves after the best prefix
+        while moves.len() > best_prefix {
+            let (u, old_group) = moves.pop().unwrap();
+            loads[groups[u]] -= demand[u];
+            loads[old_group] += demand[u];
+            groups[u] = old_group;
+        }
+        if best_gain == 0 {
+            break;
+        }
+    }
+
+    // remove the groups that have been emptied by the refinement
+    let mut renumbering = vec![usize::MAX; num_groups];
+    let mut next_group = 0;
+    for group in groups.iter_mut() {
+        if renumbering[*group] == usize::MAX {
+            renumbering[*group] = next_group;
+            next_group += 1;
+        }
+        *group = renumbering[*group];
+    }
+    Ok(groups)
+}
+
+/// Gain buckets of the Fiduccia-Mattheyses refinement: for every pair of
+/// source and target groups, the unlocked tasks in the source group sorted
+/// by the gain of moving them to the target group, i.e., the data that
+/// they exchange with the target group minus that with their own group.
+struct GainBuckets {
+    buckets: Vec<Vec<GainBucket>>,
+}
+
+/// Gains and tasks, in reverse order by decreasing gain, then increasing task.
+type GainBucket = std::collections::BTreeSet<(i64, std::cmp::Reverse<usize>)>;
+
+impl GainBuckets {
+    fn new(num_groups: usize) -> Self {
+        Self {
+            buckets: vec![vec![GainBucket::new(); num_groups]; num_groups],
+        }
+    }
+
+    /// Add a task in the given group, which exchanges `connection[g]`
+    /// with every group `g`.
+    fn insert(&mut self, u: usize, group: usize, connection: &[i64]) {
+        for (target, bucket) in self.buckets[group].iter_mut().enumerate() {
+            if target != group {
+                bucket.insert((connection[target] - connection[group], std::cmp::Reverse(u)));
+            }
+        }
+    }
+
+    /// Remove a task added with the same group and connections.
+    fn remove(&mut self, u: usize, group: usize, connection: &[i64]) {
+        for (target, bucket) in self.buckets[group].iter_mut().enumerate() {
+            if target != group {
+                bucket.remove(&(connection[target] - connection[group], std::cmp::Reverse(u)));
+            }
+        }
+    }
+
+    /// Return the gains and tasks moving from `source` to `target`,
+    /// in decreasing order of gain, then increasing order of task.
+    fn iter(&self, source: usize, target: usize) -> impl Iterator<Item = (i64, usize)> + '_ {
+        self.buckets[source][target]
+            .iter()
+            .rev()
+            .map(|(gain, u)| (*gain, u.0))
+    }
+}
+
+/// Return the total size of the arguments exchanged between tasks
+/// in different groups.
+pub fn cut_size(job: &crate::job::Job, groups: &[usize]) -> usize {
+    job.graph
+        .edge_references()
+        .filter(|x| groups[x.source().index()] != groups[x.target().index()])
+        .map(|x| x.weight().arg_size)
+        .sum()
+}
+
+/// Same as [`cut_size`], where some tasks may not be in any group, e.g.,
+/// when they are not allocated to nodes, and the arguments they exchange
+/// are not counted.
+pub fn partial_cut_size(job: &crate::job::Job, groups: &[Option<usize>]) -> usize {
+    job.graph
+        .edge_references()
+        .filter(
+            |x| match (groups[x.source().index()], groups[x.target().index()]) {
+                (Some(u), Some(v)) => u != v,
+                _ => false,
+            },
+        )
+        .map(|x| x.weight().arg_size)
+        .sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::job::{Edge, Job, Vertex};
+
+    fn group_loads(job: &Job, groups: &[usize]) -> Vec<usize> {
+        let mut loads = vec![0; groups.iter().max().map_or(0, |x| x + 1)];
+        for (task, group) in groups.iter().enumerate() {
+            loads[*group] += job
+                .graph
+                .node_weight((task as u32).into())
+                .unwrap()
+                .cpu_request;
+        }
+        loads
+    }
+
+    fn fan_in_job(state_size: usize) -> Job {
+        // two fan-in sub-DAGs connected by a light edge
+        Job::new(
+            (0..6).map(|_| Vertex::new(200, state_size)).collect(),
+            vec![
+                (0, 4, Edge::new(100)),
+                (1, 4, Edge::new(100)),
+                (2, 5, Edge::new(100)),
+                (3, 5, Edge::new(100)),
+                (4, 5, Edge::new(1)),
+            ],
+        )
+    }
+
+    #[test]
+    fn test_partition_fan_in() -> anyhow::Result<()> {
+        let job = fan_in_job(1);
+        let groups = partition(&job, Resources::new(600, 100))?;
+        assert_eq!(6, groups.len());
+        assert_eq!(vec![600, 600], group_loads(&job, &groups));
+        assert_eq!(1, cut_size(&job, &groups));
+
+        // the tasks not in a group do not count
+        let mut partial_groups = groups.iter().map(|x| Some(*x)).collect::<Vec<_>>();
+        assert_eq!(1, partial_cut_size(&job, &partial_groups));
+        partial_groups[4] = None;
+        assert_eq!(0, partial_cut_size(&job, &partial_groups));
+        partial_groups[5] = Some(groups[4]);
+        assert_eq!(200, partial_cut_size(&job, &partial_groups));
+
+        // the tasks must fit into the capacity
+        assert!(partition(&job, Resources::new(100, 100)).is_err());
+        assert!(partition(&job, Resources::new(600, 0)).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_partition_memory() -> anyhow::Result<()> {
+        // the memory allows only two tasks per group
+        let job = fan_in_job(10);
+        let groups = partition(&job, Resources::new(1200, 20))?;
+        assert_eq!(vec![400, 400, 400], group_loads(&job, &groups));
+        assert!(cut_size(&job, &groups) >= 201);
+        Ok(())
+    }
+
+    #[test]
+    fn test_partition_large() -> anyhow::Result<()> {
+        // the largest jobs allowed, with a backbone and some shortcuts
+        let num_tasks = crate::cluster::MAX_TASKS_PER_JOB;
+        let mut edges = vec![];
+        for u in 0..num_tasks - 1 {
+            edges.push((u as u32, u as u32 + 1, Edge::new(10)));
+            if u % 3 == 0 && u + 7 < num_tasks {
+                edges.push((u as u32, u as u32 + 7, Edge::new(1)));
+            }
+        }
+        let job = Job::new((0..num_tasks).map(|_| Vertex::new(100, 1)).collect(), edges);
+        let groups = partition(&job, Resources::new(1000, 100))?;
+        let loads = group_loads(&job, &groups);
+        assert_eq!(100, loads.len());
+        assert!(loads.iter().all(|x| *x == 1000));
+
+        // not worse than cutting the backbone every ten tasks
+        let chunks = (0..num_tasks).map(|u| u / 10).collect::<Vec<usize>>();
+        assert!(cut_size(&job, &groups) <= cut_size(&job, &chunks));
+        Ok(())
+    }
+
+    #[test]
+    fn test_partition_capacity() -> anyhow::Result<()> {
+        let mut jf = crate::job::JobFactory::new(42, 100.0, 100.0)?;
+        for _ in 0..200 {
+            let job = jf.make();
+            let groups = partition(&job, Resources::new(1000, 1000000))?;
+            let loads = group_loads(&job, &groups);
+            assert!(loads.iter().all(|x| *x > 0 && *x <= 1000));
+            if loads.len() == 1 {
+                assert_eq!(0, cut_size(&job, &groups));
+            }
+        }
+        Ok(())
+    }
+}
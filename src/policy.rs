@@ -0,0 +1,775 @@
+use petgraph::visit::IntoNodeReferences;
+use rand::{seq::SliceRandom, SeedableRng};
+
+use crate::cluster::{Cluster, Resources, TypeLoad};
+use crate::defragmentation::Defragmentation;
+use crate::job::Job;
+use crate::simulation::Config;
+
+/// Allocation of the tasks of the active jobs to processing nodes.
+///
+/// A new instance is created for every simulation through a [`Policy`],
+/// then the simulator notifies it of every job arrival, job departure,
+/// and periodic defragmentation, and queries its per-node view of
+/// the cluster to collect the metrics.
+///
+/// The default implementations of the methods are those of a stateful
+/// policy, which keeps track of the allocations in the [`Cluster`].
+pub trait AllocationPolicy: Send {
+    /// Allocate the tasks of a job that has just been added to the cluster.
+    fn allocate(&mut self, cluster: &mut Cluster, job_id: u64, job: &Job);
+
+    /// Allocate some of the tasks of an active job, in the given order,
+    /// e.g., when re-allocating the tasks of all the jobs together.
+    /// By default, each task is allocated to the node with the lowest ID
+    /// among those that would leave the smallest residual CPU, adding
+    /// a new node if it does not fit into any.
+    fn allocate_tasks(&mut self, cluster: &mut Cluster, job_id: u64, job: &Job, tasks: &[u32]) {
+        for task_id in tasks {
+            let demand = job.graph.node_weight((*task_id).into()).unwrap().demand();
+            let node_id = match cluster.best_fit_nodes(demand).first() {
+                Some(node_id) => *node_id,
+                None => {
+                    let node_type = self.select_node_type(cluster, demand);
+                    cluster.add_node(node_type)
+                }
+            };
+            cluster.add_task(job_id, *task_id, node_id);
+        }
+    }
+
+    /// Release the tasks of a job that is about to be removed from the cluster.
+    fn deallocate(&mut self, cluster: &mut Cluster, job_id: u64) {
+        let num_tasks = cluster.job(job_id).unwrap().graph.node_count() as u32;
+        self.deallocate_tasks(cluster, job_id, &(0..num_tasks).collect::<Vec<u32>>());
+    }
+
+    /// Release some of the tasks of an active job, e.g., when they finish
+    /// before the others in batch execution.
+    fn deallocate_tasks(&mut self, cluster: &mut Cluster, job_id: u64, tasks: &[u32]) {
+        for task_id in tasks {
+            cluster.del_task(job_id, *task_id);
+        }
+    }
+
+    /// Optimize the allocation of the active jobs with the given strategy,
+    /// which may use this policy to re-allocate them.
+    /// Return the traffic due to the migration of task state and
+    /// the number of migrations.
+    fn defragment(
+        &mut self,
+        cluster: &mut Cluster,
+        defragmentation: &Defragmentation,
+    ) -> (usize, usize) {
+        defragmentation.run(self, cluster)
+    }
+
+    /// Return the type of the node to be added to the cluster when none
+    /// of the existing ones can host the given demand.
+    /// By default, the cheapest type whose capacity is sufficient.
+    fn select_node_type(&mut self, cluster: &Cluster, demand: Resources) -> usize {
+        cluster
+            .cheapest_node_type(demand)
+            .unwrap_or_else(|| panic!("no node type can host the demand {}", demand))
+    }
+
+    /// Return the busy nodes and resources used for each node type.
+    fn type_loads(&self, cluster: &Cluster) -> Vec<TypeLoad> {
+        cluster.type_loads().to_vec()
+    }
+
+    /// Return the traffic exchanged between nodes for one invocation
+    /// of all the active jobs.
+    fn traffic(&self, cluster: &Cluster) -> f64 {
+        cluster.cross_node_traffic()
+    }
+
+    /// Return true if the nodes remain active once they have been used,
+    /// in which case the busy nodes metric is the peak value over
+    /// the simulation rather than the time average.
+    fn peak_provisioning(&self) -> bool {
+        false
+    }
+}
+
+type PolicyFactory = dyn Fn(&Config) -> Box<dyn AllocationPolicy> + Send + Sync;
+
+/// A named allocation policy, used to create a new instance
+/// of the policy for each simulation.
+#[derive(Clone)]
+pub struct Policy {
+    name: String,
+    factory: std::sync::Arc<PolicyFactory>,
+}
+
+impl Policy {
+    pub fn new<F>(name: &str, factory: F) -> Self
+    where
+        F: Fn(&Config) -> Box<dyn AllocationPolicy> + Send + Sync + 'static,
+    {
+        Self {
+            name: name.to_string(),
+            factory: std::sync::Arc::new(factory),
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Create a new instance of the policy for the given simulation.
+    pub fn make(&self, config: &Config) -> Box<dyn AllocationPolicy> {
+        (self.factory)(config)
+    }
+
+    /// Return the built-in policy with the given name.
+    pub fn from(policy: &str) -> anyhow::Result<Self> {
+        PolicyRegistry::new().get(policy)
+    }
+
+    /// Return all the built-in policies.
+    pub fn all() -> Vec<Policy> {
+        PolicyRegistry::new().all().to_vec()
+    }
+}
+
+impl std::fmt::Debug for Policy {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Policy({})", self.name)
+    }
+}
+
+impl std::fmt::Display for Policy {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.name)
+    }
+}
+
+/// Collection of the allocation policies that can be selected by name.
+///
+/// The registry is created with the built-in policies, additional
+/// policies can be added with [`PolicyRegistry::register`].
+pub struct PolicyRegistry {
+    policies: Vec<Policy>,
+}
+
+impl Default for PolicyRegistry {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl PolicyRegistry {
+    /// Create a registry with the built-in policies.
+    pub fn new() -> Self {
+        Self {
+            policies: vec![
+                Policy::new("stateless-min-nodes", |_| {
+                    Box::new(StatelessMinNodes::default())
+                }),
+                Policy::new("stateless-max-balancing", |_| {
+                    Box::new(StatelessMaxBalancing::default())
+                }),
+                Policy::new("stateful-best-fit", |config| {
+                    Box::new(StatefulBestFit::new(config.seed))
+                }),
+                Policy::new("stateful-random", |config| {
+                    Box::new(StatefulRandom::new(config.seed))
+                }),
+                Policy::new("stateful-graph-partitioning", |config| {
+                    Box::new(StatefulGraphPartitioning::new(config.seed))
+                }),
+            ],
+        }
+    }
+
+    /// Add a policy, which must have a name different from all those
+    /// already in the registry.
+    pub fn register(&mut self, policy: Policy) -> anyhow::Result<()> {
+        anyhow::ensure!(
+            self.policies.iter().all(|x| x.name != policy.name),
+            "duplicate policy: {}",
+            policy.name
+        );
+        self.policies.push(policy);
+        Ok(())
+    }
+
+    pub fn get(&self, policy: &str) -> anyhow::Result<Policy> {
+        match self.policies.iter().find(|x| x.name == policy) {
+            Some(policy) => Ok(policy.clone()),
+            None => Err(anyhow::anyhow!("unknown policy: {}", policy)),
+        }
+    }
+
+    pub fn all(&self) -> &[Policy] {
+        &self.policies
+    }
+
+    pub fn names(&self) -> Vec<&str> {
+        self.policies.iter().map(|x| x.name()).collect()
+    }
+}
+
+/// Resources requested by the running tasks in a fluid model, i.e.,
+/// all the tasks of the active jobs, except with batch execution, where
+/// they are only those started and not yet finished.
+#[derive(Default)]
+struct FluidLoad {
+    demand: Resources,
+    /// Total size of the arguments received by the running tasks.
+    arg_size: usize,
+}
+
+impl FluidLoad {
+    /// Return the resources requested by some tasks of a job and the
+    /// total size of the arguments they receive.
+    fn of(job: &Job, tasks: &[u32]) -> (Resources, usize) {
+        let mut demand = Resources::default();
+        let mut arg_size = 0;
+        for task_id in tasks {
+            let index = petgraph::graph::NodeIndex::new(*task_id as usize);
+            demand += job.graph.node_weight(index).unwrap().demand();
+            arg_size += job
+                .graph
+                .edges_directed(index, petgraph::Incoming)
+                .map(|x| x.weight().arg_size)
+                .sum::<usize>();
+        }
+        (demand, arg_size)
+    }
+
+    fn add(&mut self, job: &Job, tasks: &[u32]) {
+        let (demand, arg_size) = Self::of(job, tasks);
+        self.demand += demand;
+        self.arg_size += arg_size;
+    }
+
+    fn remove(&mut self, job: &Job, tasks: &[u32]) {
+        let (demand, arg_size) = Self::of(job, tasks);
+        self.demand -= demand;
+        self.arg_size -= arg_size;
+    }
+
+    /// Return the busy nodes and resources used, where the running tasks
+    /// are spread over the minimum number of nodes, all of the type with
+    /// the lowest price per CPU (see [`Cluster::cheapest_node_type_per_cpu`]).
+    /// The state of the tasks is not kept on the nodes, hence no memory is used.
+    fn type_loads(&self, cluster: &Cluster) -> Vec<TypeLoad> {
+        let node_type = cluster.cheapest_node_type_per_cpu();
+        let node_capacity = cluster.node_types()[node_type].capacity.cpu;
+        let total_cpu = self.demand.cpu;
+        let mut type_loads = vec![TypeLoad::default(); cluster.node_types().len()];
+        type_loads[node_type] = TypeLoad {
+            busy_nodes: total_cpu.div_ceil(node_capacity),
+            used: Resources::new(total_cpu, 0),
+        };
+        type_loads
+    }
+
+    /// Return the traffic, where all the states and arguments of the
+    /// running tasks are transferred through the network at every invocation.
+    fn traffic(&self) -> f64 {
+        (self.demand.memory + self.arg_size) as f64
+    }
+}
+
+/// Fluid model: each task is assigned a fraction of a node.
+/// Only the minimum number of nodes are kept active to match the requests.
+#[derive(Default)]
+pub struct StatelessMinNodes {
+    load: FluidLoad,
+}
+
+impl AllocationPolicy for StatelessMinNodes {
+    fn allocate(&mut self, _cluster: &mut Cluster, _job_id: u64, job: &Job) {
+        self.load.add(
+            job,
+            &(0..job.graph.node_count() as u32).collect::<Vec<u32>>(),
+        );
+    }
+
+    fn allocate_tasks(&mut self, _cluster: &mut Cluster, _job_id: u64, job: &Job, tasks: &[u32]) {
+        self.load.add(job, tasks);
+    }
+
+    fn deallocate_tasks(&mut self, cluster: &mut Cluster, job_id: u64, tasks: &[u32]) {
+        self.load.remove(cluster.job(job_id).unwrap(), tasks);
+    }
+
+    fn defragment(
+        &mut self,
+        _cluster: &mut Cluster,
+        _defragmentation: &Defragmentation,
+    ) -> (usize, usize) {
+        (0, 0)
+    }
+
+    fn type_loads(&self, cluster: &Cluster) -> Vec<TypeLoad> {
+        self.load.type_loads(cluster)
+    }
+
+    fn traffic(&self, _cluster: &Cluster) -> f64 {
+        self.load.traffic()
+    }
+}
+
+/// Same as `StatelessMinNodes` but all the required nodes
+/// always remain active.
+#[derive(Default)]
+pub struct StatelessMaxBalancing {
+    load: FluidLoad,
+}
+
+impl AllocationPolicy for StatelessMaxBalancing {
+    fn allocate(&mut self, _cluster: &mut Cluster, _job_id: u64, job: &Job) {
+        self.load.add(
+            job,
+            &(0..job.graph.node_count() as u32).collect::<Vec<u32>>(),
+        );
+    }
+
+    fn allocate_tasks(&mut self, _cluster: &mut Cluster, _job_id: u64, job: &Job, tasks: &[u32]) {
+        self.load.add(job, tasks);
+    }
+
+    fn deallocate_tasks(&mut self, cluster: &mut Cluster, job_id: u64, tasks: &[u32]) {
+        self.load.remove(cluster.job(job_id).unwrap(), tasks);
+    }
+
+    fn defragment(
+        &mut self,
+        _cluster: &mut Cluster,
+        _defragmentation: &Defragmentation,
+    ) -> (usize, usize) {
+        (0, 0)
+    }
+
+    fn type_loads(&self, cluster: &Cluster) -> Vec<TypeLoad> {
+        self.load.type_loads(cluster)
+    }
+
+    fn traffic(&self, _cluster: &Cluster) -> f64 {
+        self.load.traffic()
+    }
+
+    fn peak_provisioning(&self) -> bool {
+        true
+    }
+}
+
+/// When allocating the task of a job:
+/// - if there is another task of the same job that depends on this one
+///   in a node with sufficient residual capacity, use that node
+/// - otherwise, allocate the task to the node that minimizes the
+///   residual capacity, if any available (if not: add a new node)
+pub struct StatefulBestFit {
+    rng: rand::rngs::StdRng,
+}
+
+impl StatefulBestFit {
+    pub fn new(seed: u64) -> Self {
+        Self {
+            rng: rand::rngs::StdRng::seed_from_u64(seed + 1100000),
+        }
+    }
+}
+
+impl AllocationPolicy for StatefulBestFit {
+    fn allocate(&mut self, cluster: &mut Cluster, job_id: u64, job: &Job) {
+        let tasks = (0..job.graph.node_count() as u32).collect::<Vec<u32>>();
+        self.allocate_tasks(cluster, job_id, job, &tasks);
+    }
+
+    fn allocate_tasks(&mut self, cluster: &mut Cluster, job_id: u64, job: &Job, tasks: &[u32]) {
+        'allocation_loop: for task_id in tasks {
+            let task_id = *task_id;
+            let index = petgraph::graph::NodeIndex::new(task_id as usize);
+            let demand = job.graph.node_weight(index).unwrap().demand();
+
+            // if there is a node hosting a task which is a predecessor of this
+            // node with enough residual capacity to host this task too, then
+            // use it
+            for pred_task_id in job.graph.neighbors_directed(index, petgraph::Incoming) {
+                if let Some(pred_node_id) = cluster.allocation(job_id, pred_task_id.index() as u32)
+                {
+                    if cluster.capacity_residual(pred_node_id, demand).is_some() {
+                        cluster.add_task(job_id, task_id, pred_node_id);
+                        continue 'allocation_loop;
+                    }
+                }
+            }
+
+            // find the active node that would leaves the smallest residual
+            // CPU if this task is assigned to it, among those where the task
+            // fits in all the dimensions
+            //
+            // note that an inactive node will be selected below only if there
+            // are no active nodes that could fulfill the request, with no need
+            // of filtering on this condition explicitly, because we pick the
+            // node that leaves the smallest residual
+            let candidates = cluster.best_fit_nodes(demand);
+            match candidates.choose(&mut self.rng) {
+                Some(node_id) => {
+                    cluster.add_task(job_id, task_id, *node_id);
+                }
+                None => {
+                    let node_type = self.select_node_type(cluster, demand);
+                    let node_id = cluster.add_node(node_type);
+                    cluster.add_task(job_id, task_id, node_id);
+                }
+            }
+        }
+    }
+}
+
+/// Allocate task job by job, each assigned to a random node
+/// among those with sufficient residual capacity, otherwise
+/// a new node is added.
+pub struct StatefulRandom {
+    rng: rand::rngs::StdRng,
+}
+
+impl StatefulRandom {
+    pub fn new(seed: u64) -> Self {
+        Self {
+            rng: rand::rngs::StdRng::seed_from_u64(seed + 1100000),
+        }
+    }
+}
+
+impl AllocationPolicy for StatefulRandom {
+    fn allocate(&mut self, cluster: &mut Cluster, job_id: u64, job: &Job) {
+        let tasks = (0..job.graph.node_count() as u32).collect::<Vec<u32>>();
+        self.allocate_tasks(cluster, job_id, job, &tasks);
+    }
+
+    fn allocate_tasks(&mut self, cluster: &mut Cluster, job_id: u64, job: &Job, tasks: &[u32]) {
+        for task_id in tasks {
+            let demand = job.graph.node_weight((*task_id).into()).unwrap().demand();
+            let mut candidates = vec![];
+            for node_id in 0..cluster.nodes().len() {
+                if cluster.capacity_residual(node_id, demand).is_some() {
+                    candidates.push(node_id);
+                }
+            }
+            match candidates.choose(&mut self.rng) {
+                Some(node_id) => {
+                    cluster.add_task(job_id, *task_id, *node_id);
+                }
+                None => {
+                    // there is no node, including inactive ones, where the
+                    // task would fit
+                    let node_type = self.select_node_type(cluster, demand);
+                    let node_id = cluster.add_node(node_type);
+                    cluster.add_task(job_id, *task_id, node_id);
+                }
+            }
+        }
+    }
+}
+
+/// Partition the whole DAG of a job into groups that fit into a node,
+/// so as to minimize the size of the arguments exchanged between tasks
+/// in different groups (see [`crate::partition::partition`]), then
+/// allocate the groups in decreasing order of CPU request, each to
+/// the node that minimizes the residual CPU, if any available
+/// (if not: add a new node).
+///
+/// The job is partitioned for every node type that can host all its
+/// tasks, and the partition with the smallest cut is used, then that
+/// with the fewest groups. If no type can host all the tasks, e.g.,
+/// some need more CPU and others more memory than the other types
+/// provide, every task is a group by itself.
+///
+/// When only some tasks of a job are allocated, e.g., as they start with
+/// batch execution, the job is partitioned once and each task is allocated
+/// to a node hosting another task of its group, if it fits, otherwise to
+/// the node that minimizes the residual CPU after hosting the tasks of its
+/// group neither allocated yet nor finished, if any available (if not:
+/// add a new node).
+pub struct StatefulGraphPartitioning {
+    rng: rand::rngs::StdRng,
+    /// Group of each task of the active jobs partitioned, by job ID.
+    partitions: std::collections::BTreeMap<u64, Vec<usize>>,
+    /// Job and task IDs of the tasks released before their job ends.
+    finished: std::collections::BTreeSet<(u64, u32)>,
+}
+
+impl StatefulGraphPartitioning {
+    pub fn new(seed: u64) -> Self {
+        Self {
+            rng: rand::rngs::StdRng::seed_from_u64(seed + 1100000),
+            partitions: std::collections::BTreeMap::new(),
+            finished: std::collections::BTreeSet::new(),
+        }
+    }
+
+    /// Return the group of each task of an active job, partitioning it
+    /// only the first time, and forget the jobs no longer active.
+    fn groups(&mut self, cluster: &Cluster, job_id: u64, job: &Job) -> Vec<usize> {
+        self.partitions
+            .retain(|job_id, _groups| cluster.job(*job_id).is_some());
+        self.finished
+            .retain(|(job_id, _task_id)| cluster.job(*job_id).is_some());
+        self.partitions
+            .entry(job_id)
+            .or_insert_with(|| Self::partition(cluster, job))
+            .clone()
+    }
+
+    /// Return the group of each task of a job.
+    fn partition(cluster: &Cluster, job: &Job) -> Vec<usize> {
+        cluster
+            .node_types()
+            .iter()
+            .filter_map(|x| {
+                crate::partition::partition(job, x.capacity)
+                    .ok()
+                    .map(|groups| (x.capacity, groups))
+            })
+            .min_by_key(|(capacity, groups)| {
+                (
+                    crate::partition::cut_size(job, groups),
+                    groups.iter().max().map_or(0, |x| x + 1),
+                    std::cmp::Reverse((capacity.cpu, capacity.memory)),
+                )
+            })
+            .map_or_else(|| (0..job.graph.node_count()).collect(), |x| x.1)
+    }
+}
+
+impl AllocationPolicy for StatefulGraphPartitioning {
+    fn allocate(&mut self, cluster: &mut Cluster, job_id: u64, job: &Job) {
+        let groups = self.groups(cluster, job_id, job);
+        let num_groups = groups.iter().max().map_or(0, |x| x + 1);
+        let mut tasks = vec![vec![]; num_groups];
+        let mut demand = vec![Resources::default(); num_groups];
+        for (index, weight) in job.graph.node_references() {
+            tasks[groups[index.index()]].push(index.index() as u32);
+            demand[groups[index.index()]] += weight.demand();
+        }
+        let mut order = (0..num_groups).collect::<Vec<usize>>();
+        order.sort_by(|a, b| demand[*b].cpu.cmp(&demand[*a].cpu));
+
+        for group in order {
+            let candidates = cluster.best_fit_nodes(demand[group]);
+            let node_id = match candidates.choose(&mut self.rng) {
+                Some(node_id) => *node_id,
+                None => {
+                    let node_type = self.select_node_type(cluster, demand[group]);
+                    cluster.add_node(node_type)
+                }
+            };
+            for task_id in &tasks[group] {
+                cluster.add_task(job_id, *task_id, node_id);
+            }
+        }
+    }
+
+    fn allocate_tasks(&mut self, cluster: &mut Cluster, job_id: u64, job: &Job, tasks: &[u32]) {
+        let groups = self.groups(cluster, job_id, job);
+        for task_id in tasks {
+            let group = groups[*task_id as usize];
+            let demand = job.graph.node_weight((*task_id).into()).unwrap().demand();
+
+            // nodes hosting the other tasks of the group, and demand of those
+            // not allocated yet, including this one, which have not finished
+            let mut group_nodes = vec![];
+            let mut group_demand = Resources::default();
+            for (index, weight) in job.graph.node_references() {
+                let other_task_id = index.index() as u32;
+                if groups[index.index()] == group {
+                    match cluster.allocation(job_id, other_task_id) {
+                        Some(node_id) => group_nodes.push(node_id),
+                        None if !self.finished.contains(&(job_id, other_task_id)) => {
+                            group_demand += weight.demand()
+                        }
+                        None => {}
+                    }
+                }
+            }
+            group_nodes.sort();
+            group_nodes.dedup();
+
+            let node_id = match group_nodes
+                .into_iter()
+                .find(|x| cluster.capacity_residual(*x, demand).is_some())
+            {
+                Some(node_id) => node_id,
+                None => {
+                    let candidates = cluster.best_fit_nodes(group_demand);
+                    match candidates.choose(&mut self.rng) {
+                        Some(node_id) => *node_id,
+                        None => {
+                            let node_type = self.select_node_type(cluster, group_demand);
+                            cluster.add_node(node_type)
+                        }
+                    }
+                }
+            };
+            cluster.add_task(job_id, *task_id, node_id);
+        }
+    }
+
+    fn deallocate_tasks(&mut self, cluster: &mut Cluster, job_id: u64, tasks: &[u32]) {
+        for task_id in tasks {
+            cluster.del_task(job_id, *task_id);
+            self.finished.insert((job_id, *task_id));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct FirstNode {}
+
+    impl AllocationPolicy for FirstNode {
+        fn allocate(&mut self, cluster: &mut Cluster, job_id: u64, job: &Job) {
+            if cluster.nodes().is_empty() {
+                cluster.add_node(0);
+            }
+            for task_id in job.graph.node_indices() {
+                cluster.add_task(job_id, task_id.index() as u32, 0);
+            }
+        }
+    }
+
+    #[test]
+    fn test_policy_registry() -> anyhow::Result<()> {
+        let mut registry = PolicyRegistry::new();
+        assert_eq!(
+            vec![
+                "stateless-min-nodes",
+                "stateless-max-balancing",
+                "stateful-best-fit",
+                "stateful-random",
+                "stateful-graph-partitioning"
+            ],
+            registry.names()
+        );
+        assert!(registry.get("first-node").is_err());
+
+        registry.register(Policy::new("first-node", |_| Box::new(FirstNode {})))?;
+        assert!(registry
+            .register(Policy::new("first-node", |_| Box::new(FirstNode {})))
+            .is_err());
+        assert_eq!("first-node", registry.get("first-node")?.name());
+        assert_eq!(6, registry.all().len());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_policy_graph_partitioning_node_types() -> anyhow::Result<()> {
+        let node_types = crate::cluster::NodeType::from_file("data/node_types.csv")?;
+        let mut policy = StatefulGraphPartitioning::new(42);
+
+        // only a highmem node can host the first task, and all the tasks
+        let job = Job::new(
+            vec![
+                crate::job::Vertex::new(100, 100000),
+                crate::job::Vertex::new(100, 1000),
+                crate::job::Vertex::new(100, 1000),
+            ],
+            vec![
+                (0, 1, crate::job::Edge::new(10)),
+                (1, 2, crate::job::Edge::new(10)),
+            ],
+        );
+        let mut cluster = Cluster::new(node_types.clone());
+        cluster.insert_job(0, job.clone());
+        policy.allocate(&mut cluster, 0, &job);
+        assert_eq!(1, cluster.nodes().len());
+        assert_eq!("highmem", node_types[cluster.nodes()[0].node_type].name);
+        assert_eq!(0.0, cluster.cross_node_traffic());
+
+        // no node type can host both the first and the last task
+        let job = Job::new(
+            vec![
+                crate::job::Vertex::new(100, 100000),
+                crate::job::Vertex::new(100, 1000),
+                crate::job::Vertex::new(1200, 1000),
+            ],
+            vec![
+                (0, 1, crate::job::Edge::new(10)),
+                (1, 2, crate::job::Edge::new(10)),
+            ],
+        );
+        let mut cluster = Cluster::new(node_types.clone());
+        cluster.insert_job(1, job.clone());
+        policy.allocate(&mut cluster, 1, &job);
+        let allocated_types = (0..3)
+            .map(|x| {
+                node_types[cluster.nodes()[cluster.allocation(1, x).unwrap()].node_type]
+                    .name
+                    .as_str()
+            })
+            .collect::<Vec<&str>>();
+        assert_eq!("highmem", allocated_types[0]);
+        assert_eq!("large", allocated_types[2]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_policy_graph_partitioning_tasks() {
+        // two fan-in sub-DAGs connected by a light edge, each filling a node
+        let job = Job::new(
+            (0..6).map(|_| crate::job::Vertex::new(200, 1)).collect(),
+            vec![
+                (0, 4, crate::job::Edge::new(100)),
+                (1, 4, crate::job::Edge::new(100)),
+                (2, 5, crate::job::Edge::new(100)),
+                (3, 5, crate::job::Edge::new(100)),
+                (4, 5, crate::job::Edge::new(1)),
+            ],
+        );
+        let node_types = vec![crate::cluster::NodeType::new(
+            "default",
+            Resources::new(600, 1000),
+            50.0,
+            100.0,
+            1.0,
+        )];
+        let check = |cluster: &Cluster| {
+            let nodes = (0..6)
+                .map(|x| cluster.allocation(0, x).unwrap())
+                .collect::<Vec<usize>>();
+            assert_eq!(nodes[0], nodes[1]);
+            assert_eq!(nodes[0], nodes[4]);
+            assert_eq!(nodes[2], nodes[3]);
+            assert_eq!(nodes[2], nodes[5]);
+            assert_ne!(nodes[0], nodes[2]);
+            assert_eq!(1.0, cluster.cross_node_traffic());
+        };
+
+        // the tasks start one by one, in an order that would mix the groups
+        let mut policy = StatefulGraphPartitioning::new(42);
+        let mut cluster = Cluster::new(node_types);
+        cluster.insert_job(0, job.clone());
+        for task_id in [0, 2, 1, 3, 4, 5] {
+            policy.allocate_tasks(&mut cluster, 0, &job, &[task_id]);
+        }
+        assert_eq!(2, cluster.nodes().len());
+        check(&cluster);
+
+        // and so are they re-allocated in decreasing order of CPU
+        crate::defragmentation::full_repack(
+            &mut policy,
+            &mut cluster,
+            crate::defragmentation::RepackingOrder::TaskCpu,
+        );
+        check(&cluster);
+    }
+
+    #[test]
+    fn test_policy_from_name() {
+        for policy in Policy::all() {
+            assert_eq!(policy.name(), Policy::from(policy.name()).unwrap().name());
+        }
+        assert!(Policy::from("unknown").is_err());
+    }
+}
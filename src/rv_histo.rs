@@ -105,6 +105,7 @@ impl RvHisto {
 }
 
 #[cfg(test)]
+#[allow(clippy::needless_borrow, clippy::expect_fun_call)]
 mod tests {
     use super::*;
 
@@ -147,7 +148,7 @@ mod tests {
             };
         }
         assert_eq!(0, counts.iter().filter(|x| *x.0 < 2 || *x.0 > 303).count());
-        assert_eq!(100000_usize, counts.iter().map(|x| x.1).sum());
+        assert_eq!(100000_usize, counts.iter().map(|x| x.1).sum::<usize>());
     }
 
     #[test]
@@ -1,115 +1,216 @@
-use petgraph::visit::{EdgeRef, IntoNodeReferences};
-use rand::{distributions::Distribution, seq::SliceRandom, SeedableRng};
+use rand::{distributions::Distribution, SeedableRng};
 
-#[derive(Debug, Clone)]
-pub enum Policy {
-    /// Fluid model: each task is assigned a fraction of a node.
-    /// Only the minimum number of nodes are kept active to match the requests.
-    StatelessMinNodes,
-    /// Same as `Policy::StatelessMinNodes` but all the required nodes
-    /// always remain active.
-    StatelessMaxBalancing,
-    /// When allocating the task of a job:
-    /// - if there is another task of the same job that depends on this one
-    ///   in a node with sufficient residual capacity, use that node
-    /// - otherwise, allocate the task to the node that minimizes the
-    ///   residual capacity, if any available (if not: add a new node)
-    StatefulBestFit,
-    /// Allocate task job by job, each assigned to a random node
-    /// among those with sufficient residual capacity, otherwise
-    /// a new node is added.
-    StatefulRandom,
-}
-
-impl Policy {
-    pub fn from(policy: &str) -> anyhow::Result<Self> {
-        match policy {
-            "stateless-min-nodes" => Ok(Policy::StatelessMinNodes),
-            "stateless-max-balancing" => Ok(Policy::StatelessMaxBalancing),
-            "stateful-best-fit" => Ok(Policy::StatefulBestFit),
-            "stateful-random" => Ok(Policy::StatefulRandom),
-            _ => Err(anyhow::anyhow!("unknown policy: {}", policy)),
-        }
-    }
-
-    pub fn all() -> Vec<Policy> {
-        vec![
-            Policy::StatelessMinNodes,
-            Policy::StatelessMaxBalancing,
-            Policy::StatefulBestFit,
-            Policy::StatefulRandom,
-        ]
-    }
-}
+use crate::policy::Policy;
+use crate::warm_up::WarmUp;
 
-impl std::fmt::Display for Policy {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(
-            f,
-            "{}",
-            match self {
-                Policy::StatelessMinNodes => "stateless-min-nodes",
-                Policy::StatelessMaxBalancing => "stateless-max-balancing",
-                Policy::StatefulBestFit => "stateful-best-fit",
-                Policy::StatefulRandom => "stateful-random",
-            }
-        )
-    }
-}
-
-#[derive(PartialEq, Eq)]
+/// Event of the simulation, with times in s.
+#[derive(PartialEq)]
 enum Event {
     /// A new job arrives.
     /// 0: Event time.
-    JobStart(u64),
+    JobStart(f64),
     /// An active job ends.
     /// 0: Event time.
     /// 1: Job ID.
-    JobEnd(u64, u64),
+    JobEnd(f64, u64),
+    /// A task of an active job starts, with batch execution.
+    /// 0: Event time.
+    /// 1: Job ID.
+    /// 2: Task ID.
+    TaskStart(f64, u64, u32),
+    /// A task of an active job finishes, with batch execution.
+    /// 0: Event time.
+    /// 1: Job ID.
+    /// 2: Task ID.
+    TaskEnd(f64, u64, u32),
     /// The simulation ends.
     /// 0: Event time.
-    ExperimentEnd(u64),
+    ExperimentEnd(f64),
     /// Defragmentation occurs.
     /// 0: Event time.
-    Defragmentation(u64),
+    Defragmentation(f64),
+    /// The transfer of the state of a migrated task ends, until which
+    /// the task holds resources on both the source and destination nodes.
+    /// 0: Event time.
+    /// 1: Source node ID.
+    /// 2: Resources of the task.
+    MigrationEnd(f64, usize, crate::cluster::Resources),
+    /// The metrics are sampled.
+    /// 0: Event time.
+    Sample(f64),
+    /// The warm-up period ends.
+    /// 0: Event time.
+    WarmUpEnd(f64),
 }
 
 impl Event {
-    fn time(&self) -> u64 {
+    fn time(&self) -> f64 {
         match self {
             Self::JobStart(t)
             | Self::JobEnd(t, _)
+            | Self::TaskStart(t, _, _)
+            | Self::TaskEnd(t, _, _)
             | Self::ExperimentEnd(t)
-            | Self::Defragmentation(t) => *t,
+            | Self::Defragmentation(t)
+            | Self::MigrationEnd(t, _, _)
+            | Self::Sample(t)
+            | Self::WarmUpEnd(t) => *t,
+        }
+    }
+
+    /// Return the priority of the event among those at the same time,
+    /// the lower the earlier, so that the end of the simulation and the
+    /// collection of metrics do not depend on the order of insertion.
+    fn priority(&self) -> u8 {
+        match self {
+            Self::ExperimentEnd(_) => 0,
+            Self::WarmUpEnd(_) => 1,
+            Self::Sample(_) => 2,
+            _ => 3,
         }
     }
 }
 
-#[allow(clippy::non_canonical_partial_ord_impl)]
+impl Eq for Event {}
+
 impl PartialOrd for Event {
     fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
-        other.time().partial_cmp(&self.time())
+        Some(self.cmp(other))
     }
 }
 
 impl Ord for Event {
     fn cmp(&self, other: &Self) -> std::cmp::Ordering {
-        self.partial_cmp(other).unwrap()
+        other
+            .time()
+            .total_cmp(&self.time())
+            .then_with(|| other.priority().cmp(&self.priority()))
+    }
+}
+
+/// Sample of the metrics at a given time of the simulation.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Sample {
+    /// Simulated time, in s.
+    pub time: f64,
+    pub busy_nodes: usize,
+    pub active_jobs: usize,
+    /// Tasks of the active jobs or, with batch execution, tasks running.
+    pub active_tasks: usize,
+    /// Rate of the traffic between nodes due to the invocations of the
+    /// active jobs, in MB/s: the arguments between tasks on different nodes
+    /// or, with stateless policies, all the states and arguments, as in the
+    /// total traffic of the [`Output`], but without state migrations.
+    pub traffic_rate: f64,
+    /// CPU used on all the nodes.
+    pub cpu_used: usize,
+    /// Memory used on all the nodes, in MB.
+    pub memory_used: usize,
+}
+
+impl Sample {
+    pub fn header() -> &'static str {
+        "time,busy-nodes,active-jobs,active-tasks,traffic-rate-mb-per-s,cpu-used,memory-used"
+    }
+}
+
+impl std::fmt::Display for Sample {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{},{},{},{},{},{},{}",
+            self.time,
+            self.busy_nodes,
+            self.active_jobs,
+            self.active_tasks,
+            self.traffic_rate,
+            self.cpu_used,
+            self.memory_used
+        )
     }
 }
 
 #[derive(Debug)]
 pub struct Output {
     pub seed: u64,
+    /// Number of jobs arrived during the simulation.
+    pub num_jobs: u64,
+    /// Duration of the warm-up period excluded from the metrics, in s.
+    pub warm_up: f64,
     pub avg_busy_nodes: f64,
     pub total_traffic: f64,
     pub migration_rate: f64,
+    /// Average number of busy nodes freed by a defragmentation round.
+    pub avg_freed_nodes: f64,
+    /// Order in which tasks are re-allocated in a full repack.
+    pub repacking_order: crate::defragmentation::RepackingOrder,
+    /// Number of defragmentation rounds carried out.
+    pub defragmentation_rounds: usize,
+    /// Time average of the fragmentation index.
+    pub avg_fragmentation_index: f64,
+    /// Time spent by nodes that are busy only because of tasks
+    /// migrating away from them, in s.
+    pub migration_node_seconds: f64,
+    pub cpu_utilization: f64,
+    pub memory_utilization: f64,
+    /// Name of the node type and busy nodes of that type.
+    pub avg_busy_nodes_per_type: Vec<(String, f64)>,
+    pub cost: f64,
+    /// Energy consumed by the nodes, in kWh.
+    pub node_energy: f64,
+    /// Energy consumed to transfer data between nodes, in kWh.
+    pub network_energy: f64,
     pub execution_time: f64,
+    /// Metrics sampled periodically, not included in the CSV row.
+    pub samples: Vec<Sample>,
 }
 
 impl Output {
+    /// Return the names of the numeric metrics, as in the header.
+    pub fn metrics() -> &'static [&'static str] {
+        &[
+            "num-jobs",
+            "warm-up",
+            "avg-busy-nodes",
+            "total-traffic",
+            "migration-rate",
+            "avg-freed-nodes",
+            "defragmentation-rounds",
+            "avg-fragmentation-index",
+            "migration-node-seconds",
+            "cpu-utilization",
+            "memory-utilization",
+            "cost",
+            "node-energy",
+            "network-energy",
+            "execution-time",
+        ]
+    }
+
+    /// Return the value of a numeric metric given its name in the header.
+    pub fn metric(&self, name: &str) -> Option<f64> {
+        match name {
+            "num-jobs" => Some(self.num_jobs as f64),
+            "warm-up" => Some(self.warm_up),
+            "avg-busy-nodes" => Some(self.avg_busy_nodes),
+            "total-traffic" => Some(self.total_traffic),
+            "migration-rate" => Some(self.migration_rate),
+            "avg-freed-nodes" => Some(self.avg_freed_nodes),
+            "defragmentation-rounds" => Some(self.defragmentation_rounds as f64),
+            "avg-fragmentation-index" => Some(self.avg_fragmentation_index),
+            "migration-node-seconds" => Some(self.migration_node_seconds),
+            "cpu-utilization" => Some(self.cpu_utilization),
+            "memory-utilization" => Some(self.memory_utilization),
+            "cost" => Some(self.cost),
+            "node-energy" => Some(self.node_energy),
+            "network-energy" => Some(self.network_energy),
+            "execution-time" => Some(self.execution_time),
+            _ => None,
+        }
+    }
+
     pub fn header() -> &'static str {
-        "seed,avg-busy-nodes,total-traffic,migration-rate,execution-time"
+        "seed,num-jobs,warm-up,avg-busy-nodes,total-traffic,migration-rate,avg-freed-nodes,repacking-order,defragmentation-rounds,avg-fragmentation-index,migration-node-seconds,cpu-utilization,memory-utilization,avg-busy-nodes-per-type,cost,node-energy,network-energy,execution-time"
     }
 }
 
@@ -117,11 +218,28 @@ impl std::fmt::Display for Output {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(
             f,
-            "{},{},{},{},{}",
+            "{},{},{},{},{},{},{},{},{},{},{},{},{},{},{},{},{},{}",
             self.seed,
+            self.num_jobs,
+            self.warm_up,
             self.avg_busy_nodes,
             self.total_traffic,
             self.migration_rate,
+            self.avg_freed_nodes,
+            self.repacking_order,
+            self.defragmentation_rounds,
+            self.avg_fragmentation_index,
+            self.migration_node_seconds,
+            self.cpu_utilization,
+            self.memory_utilization,
+            self.avg_busy_nodes_per_type
+                .iter()
+                .map(|(name, busy_nodes)| format!("{}={}", name, busy_nodes))
+                .collect::<Vec<String>>()
+                .join(";"),
+            self.cost,
+            self.node_energy,
+            self.network_energy,
             self.execution_time
         )
     }
@@ -130,17 +248,40 @@ impl std::fmt::Display for Output {
 #[derive(Debug)]
 pub struct Config {
     /// The duration of the simulation, in s.
-    pub duration: u64,
+    pub duration: f64,
     /// The average lifetime of a job, in s.
     pub job_lifetime: f64,
+    /// How the tasks of a job are executed during its lifetime.
+    pub job_execution: crate::job::JobExecution,
     /// The average interval between two jobs, in s.
     pub job_interarrival: f64,
+    /// The profile over time of the job arrival rate.
+    pub arrival_profile: crate::arrival::ArrivalProfile,
+    /// The distribution of the interval between consecutive job arrivals.
+    pub interarrival_distribution: crate::arrival::InterarrivalDistribution,
     /// The rate at which the job is executed within its lifetime, in Hz.
     pub job_invocation_rate: f64,
-    /// The capacity of each processing node, every 100 unit means 1 core
-    pub node_capacity: usize,
+    /// The catalog of the types of processing nodes.
+    pub node_types: Vec<crate::cluster::NodeType>,
+    /// The energy consumed to transfer one byte between two nodes, in uJ.
+    pub network_energy: f64,
     /// The periodic interval at which defragmentation occures, in s.
-    pub defragmentation_interval: u64,
+    pub defragmentation_interval: f64,
+    /// If set, defragmentation occurs only when the fragmentation index
+    /// exceeds this threshold, with rounds spaced by at least
+    /// the defragmentation interval.
+    pub defragmentation_threshold: Option<f64>,
+    /// How the active jobs are re-allocated during defragmentation.
+    pub defragmentation_mode: crate::defragmentation::DefragmentationMode,
+    /// The order in which tasks are re-allocated in a full repack.
+    pub repacking_order: crate::defragmentation::RepackingOrder,
+    /// The bandwidth to transfer the state of a migrated task, in MB/s,
+    /// or `None` if migrations are instantaneous.
+    pub migration_bandwidth: Option<f64>,
+    /// The maximum migrations in a round of incremental defragmentation.
+    pub migration_budget: crate::defragmentation::MigrationBudget,
+    /// How the remaining lifetime of jobs is known in cost/benefit defragmentation.
+    pub remaining_lifetime: crate::defragmentation::RemainingLifetime,
     /// The task allocation policy.
     pub policy: Policy,
     /// The state size multiplier applied to the task memory size.
@@ -149,29 +290,135 @@ pub struct Config {
     pub arg_mul: f64,
     /// The seed to initialize pseudo-random number generators.
     pub seed: u64,
+    /// The warm-up period at the beginning of the simulation, during which
+    /// the metrics are not collected.
+    pub warm_up: crate::warm_up::WarmUp,
+    /// The interval at which the metrics are sampled, in s,
+    /// or `None` if the metrics are not sampled.
+    pub sampling_interval: Option<f64>,
+    /// The name of the JSON Lines file where to save the trace of events,
+    /// or `None` if the trace is not saved.
+    pub trace: Option<String>,
+    /// The jobs of the Alibaba cluster trace 2018, read once with
+    /// [`crate::alibaba::read_batch_tasks`] and shared by all the seeds,
+    /// which are replayed at their arrival times, with their own
+    /// lifetimes and task durations, or `None` if the jobs are synthetic.
+    pub alibaba_trace: Option<std::sync::Arc<Vec<crate::alibaba::TraceJob>>>,
+    /// The name of the file with the catalog of jobs from which those
+    /// arriving are drawn with given mix probabilities, read with
+    /// [`crate::catalog::JobCatalog::from_file`], or `None` if the jobs are
+    /// synthetic. The state and argument size multipliers do not apply.
+    pub job_catalog: Option<String>,
 }
 
-#[derive(Debug)]
-struct Node {
-    pub jobs: Vec<(u64, u32)>, // job ID, task ID within the job
+/// Metrics accumulated since the beginning of the simulation, except for
+/// the maximum busy nodes, which are since the last checkpoint.
+#[derive(Debug, Clone)]
+struct Counters {
+    avg_busy_nodes: f64,
+    max_busy_nodes: usize,
+    avg_busy_nodes_per_type: Vec<f64>,
+    max_busy_nodes_per_type: Vec<usize>,
+    avg_cpu_used: f64,
+    avg_memory_used: f64,
+    node_energy: f64,
+    total_traffic: f64,
+    migration_rate: usize,
+    freed_nodes: i64,
+    num_defragmentations: usize,
+    avg_fragmentation_index: f64,
+    migration_node_seconds: f64,
 }
 
-impl Node {
-    fn is_active(&self) -> bool {
-        !self.jobs.is_empty()
+impl Counters {
+    fn new(num_node_types: usize) -> Self {
+        Self {
+            avg_busy_nodes: 0.0,
+            max_busy_nodes: 0,
+            avg_busy_nodes_per_type: vec![0.0; num_node_types],
+            max_busy_nodes_per_type: vec![0; num_node_types],
+            avg_cpu_used: 0.0,
+            avg_memory_used: 0.0,
+            node_energy: 0.0,
+            total_traffic: 0.0,
+            migration_rate: 0,
+            freed_nodes: 0,
+            num_defragmentations: 0,
+            avg_fragmentation_index: 0.0,
+            migration_node_seconds: 0.0,
+        }
+    }
+
+    /// Return a checkpoint of the current metrics and restart the
+    /// computation of the maximum busy nodes.
+    fn checkpoint(&mut self) -> Self {
+        let ret = self.clone();
+        self.max_busy_nodes = 0;
+        self.max_busy_nodes_per_type.fill(0);
+        ret
     }
+
+    /// Return the metrics accumulated after the first of the given
+    /// checkpoints, which must be all those taken since, in order.
+    fn since(&self, checkpoints: &[Self]) -> Self {
+        let mut ret = self.clone();
+        if let Some(first) = checkpoints.first() {
+            ret.avg_busy_nodes -= first.avg_busy_nodes;
+            for (x, y) in ret
+                .avg_busy_nodes_per_type
+                .iter_mut()
+                .zip(first.avg_busy_nodes_per_type.iter())
+            {
+                *x -= y;
+            }
+            ret.avg_cpu_used -= first.avg_cpu_used;
+            ret.avg_memory_used -= first.avg_memory_used;
+            ret.node_energy -= first.node_energy;
+            ret.total_traffic -= first.total_traffic;
+            ret.migration_rate -= first.migration_rate;
+            ret.freed_nodes -= first.freed_nodes;
+            ret.num_defragmentations -= first.num_defragmentations;
+            ret.avg_fragmentation_index -= first.avg_fragmentation_index;
+            ret.migration_node_seconds -= first.migration_node_seconds;
+        }
+        for checkpoint in checkpoints.iter().skip(1) {
+            ret.max_busy_nodes = usize::max(ret.max_busy_nodes, checkpoint.max_busy_nodes);
+            for (x, y) in ret
+                .max_busy_nodes_per_type
+                .iter_mut()
+                .zip(checkpoint.max_busy_nodes_per_type.iter())
+            {
+                *x = usize::max(*x, *y);
+            }
+        }
+        ret
+    }
+}
+
+/// Progress of an active job with batch execution.
+struct BatchJob {
+    /// Duration of each task, in s.
+    durations: Vec<f64>,
+    /// Number of tasks on which each task depends that have not finished yet.
+    pending: Vec<usize>,
+    /// Number of tasks that have not finished yet.
+    remaining: usize,
 }
 
 pub struct Simulation {
     job_factory: crate::job::JobFactory,
-    job_interarrival_rng: rand::rngs::StdRng,
+    arrival_process: crate::arrival::ArrivalProcess,
     job_lifetime_rng: rand::rngs::StdRng,
-    active_jobs: std::collections::HashMap<u64, crate::job::Job>,
-
-    // internal data structures used only with stateful policies
-    nodes: Vec<Node>,
-    allocations: std::collections::HashMap<u64, usize>, // key: hash of job ID and task ID; value: node ID
-    allocate_rng: rand::rngs::StdRng,
+    /// Duration of the tasks, only with batch execution.
+    task_duration_rv: Option<crate::rv_histo::RvHisto>,
+    /// Jobs still to be replayed from a trace, if any.
+    replay: Option<std::collections::VecDeque<crate::alibaba::TraceJob>>,
+    /// Catalog from which the jobs are drawn, if any.
+    job_catalog: Option<crate::catalog::JobCatalog>,
+    cluster: crate::cluster::Cluster,
+    policy: Box<dyn crate::policy::AllocationPolicy>,
+    defragmentation: crate::defragmentation::Defragmentation,
+    trace: crate::trace::Trace,
 
     // configuration
     config: Config,
@@ -179,16 +426,65 @@ pub struct Simulation {
 
 impl Simulation {
     pub fn new(config: Config) -> anyhow::Result<Self> {
-        anyhow::ensure!(config.duration > 0, "vanishing duration");
+        anyhow::ensure!(config.duration > 0.0, "vanishing duration");
         anyhow::ensure!(
             config.job_interarrival > 0.0,
             "vanishing avg job interarrival time"
         );
         anyhow::ensure!(config.job_lifetime > 0.0, "vanishing avg job lifetime");
         anyhow::ensure!(
-            config.defragmentation_interval > 0,
+            config.defragmentation_interval > 0.0,
             "vanishing defragmentation interval"
         );
+        anyhow::ensure!(
+            config.defragmentation_threshold.is_none_or(|x| x >= 1.0),
+            "defragmentation threshold smaller than 1"
+        );
+        anyhow::ensure!(
+            config.migration_bandwidth.is_none_or(|x| x > 0.0),
+            "vanishing migration bandwidth"
+        );
+        anyhow::ensure!(
+            config.sampling_interval.is_none_or(|x| x > 0.0),
+            "vanishing sampling interval"
+        );
+        match config.warm_up {
+            WarmUp::None => {}
+            WarmUp::Fixed(warm_up) => anyhow::ensure!(
+                warm_up >= 0.0 && warm_up < config.duration,
+                "warm-up not shorter than the duration"
+            ),
+            WarmUp::Mser5 => anyhow::ensure!(
+                config.sampling_interval.is_some(),
+                "warm-up detection requires sampling"
+            ),
+        }
+        anyhow::ensure!(
+            config.defragmentation_mode != crate::defragmentation::DefragmentationMode::CostBenefit
+                || config.remaining_lifetime
+                    != crate::defragmentation::RemainingLifetime::Estimated
+                || (config.job_execution == crate::job::JobExecution::Pipeline
+                    && config.alibaba_trace.is_none()),
+            "estimated remaining lifetimes require exponential job lifetimes"
+        );
+        anyhow::ensure!(
+            config.alibaba_trace.is_none() || config.job_catalog.is_none(),
+            "cannot replay a trace and draw jobs from a catalog together"
+        );
+        anyhow::ensure!(!config.node_types.is_empty(), "no node types");
+        anyhow::ensure!(config.network_energy >= 0.0, "negative network energy");
+        for node_type in &config.node_types {
+            anyhow::ensure!(
+                node_type.capacity.cpu > 0 && node_type.capacity.memory > 0,
+                "vanishing capacity of node type {}",
+                node_type.name
+            );
+            anyhow::ensure!(
+                node_type.idle_power >= 0.0 && node_type.peak_power >= node_type.idle_power,
+                "invalid power of node type {}",
+                node_type.name
+            );
+        }
 
         Ok(Self {
             job_factory: crate::job::JobFactory::new(
@@ -196,54 +492,176 @@ impl Simulation {
                 config.state_mul,
                 config.arg_mul,
             )?,
-            job_interarrival_rng: rand::rngs::StdRng::seed_from_u64(config.seed),
+            arrival_process: crate::arrival::ArrivalProcess::new(
+                config.arrival_profile.clone(),
+                config.interarrival_distribution,
+                config.job_interarrival,
+                config.seed,
+            )?,
             job_lifetime_rng: rand::rngs::StdRng::seed_from_u64(config.seed + 1000000),
-            active_jobs: std::collections::HashMap::new(),
-            nodes: vec![],
-            allocations: std::collections::HashMap::new(),
-            allocate_rng: rand::rngs::StdRng::seed_from_u64(config.seed + 1100000),
+            task_duration_rv: match config.job_execution {
+                crate::job::JobExecution::Pipeline => None,
+                crate::job::JobExecution::Batch => Some(crate::rv_histo::RvHisto::from_file(
+                    config.seed + 1200000,
+                    "data/task_duration_dist.dat",
+                )?),
+            },
+            replay: match &config.alibaba_trace {
+                Some(jobs) => {
+                    anyhow::ensure!(!jobs.is_empty(), "no jobs in the trace");
+                    for trace_job in jobs.iter() {
+                        trace_job.job.validate(&config.node_types).map_err(|err| {
+                            anyhow::anyhow!("invalid job {} in the trace: {}", trace_job.name, err)
+                        })?;
+                    }
+                    Some(jobs.iter().cloned().collect())
+                }
+                None => None,
+            },
+            job_catalog: match &config.job_catalog {
+                Some(filename) => {
+                    let job_catalog =
+                        crate::catalog::JobCatalog::from_file(config.seed + 1300000, filename)?;
+                    for job in job_catalog.jobs() {
+                        job.validate(&config.node_types).map_err(|err| {
+                            anyhow::anyhow!("invalid job in catalog {}: {}", filename, err)
+                        })?;
+                    }
+                    Some(job_catalog)
+                }
+                None => None,
+            },
+            cluster: crate::cluster::Cluster::new(config.node_types.clone()),
+            policy: config.policy.make(&config),
+            defragmentation: crate::defragmentation::Defragmentation::from_config(&config),
+            trace: match &config.trace {
+                Some(filename) => crate::trace::Trace::from_file(filename)?,
+                None => crate::trace::Trace::disabled(),
+            },
             config,
         })
     }
 
+    /// Return the number of busy nodes of any type.
+    fn busy_nodes(&self) -> usize {
+        self.policy
+            .type_loads(&self.cluster)
+            .iter()
+            .map(|x| x.busy_nodes)
+            .sum()
+    }
+
     /// Run a simulation.
     pub fn run(&mut self) -> Output {
         // create the event queue and push initial events
         let mut events = std::collections::BinaryHeap::new();
-        events.push(Event::JobStart(0));
+        events.push(Event::JobStart(0.0));
         events.push(Event::ExperimentEnd(self.config.duration));
-        events.push(Event::Defragmentation(self.config.defragmentation_interval));
+        let defragmentation_enabled =
+            self.config.defragmentation_mode != crate::defragmentation::DefragmentationMode::None;
+        if defragmentation_enabled && self.config.defragmentation_threshold.is_none() {
+            events.push(Event::Defragmentation(self.config.defragmentation_interval));
+        }
+        if let Some(sampling_interval) = self.config.sampling_interval {
+            events.push(Event::Sample(sampling_interval));
+        }
+        if let WarmUp::Fixed(warm_up) = self.config.warm_up {
+            events.push(Event::WarmUpEnd(warm_up));
+        }
 
         // initialize simulated time and ID of the first job
-        let mut now = 0;
+        let mut now = 0.0;
         let mut job_id = 0;
 
         // configure random variables for workload generation
-        let job_interarrival_rv = rand_distr::Exp::new(1.0 / self.config.job_interarrival).unwrap();
         let job_duration_rv = rand_distr::Exp::new(1.0 / self.config.job_lifetime).unwrap();
 
-        // initialize metric counters
-        let mut avg_busy_nodes = 0.0;
-        let mut max_busy_nodes = 0;
-        let mut total_traffic = 0.0;
-        let mut migration_rate = 0;
+        // initialize metric counters, with checkpoints taken at the
+        // beginning, at every sample, and at the end of the warm-up period
+        let num_node_types = self.config.node_types.len();
+        let mut counters = Counters::new(num_node_types);
+        let mut checkpoints = vec![(0.0, counters.clone())];
+        let mut migrations_in_progress = 0;
+        let mut samples = vec![];
+
+        // with batch execution: progress of the active jobs and
+        // number of tasks running
+        let mut batch_jobs = std::collections::HashMap::new();
+        let mut running_tasks = 0;
+
+        // with threshold-triggered defragmentation: at most one round is
+        // scheduled at a time, not earlier than this time
+        let mut defragmentation_pending = false;
+        let mut next_defragmentation = 0.0;
 
         // simulation loop
         let real_now = std::time::Instant::now();
         'main_loop: loop {
             if let Some(event) = events.pop() {
-                let stat_interval = (event.time() - now) as f64;
+                let stat_interval = event.time() - now;
                 now = event.time();
-                let (busy_nodes, traffic) = self.compute_stats(self.config.node_capacity);
-                avg_busy_nodes += busy_nodes as f64 * stat_interval; // unit: s
-                max_busy_nodes = usize::max(max_busy_nodes, busy_nodes);
-                total_traffic += traffic * self.config.job_invocation_rate * stat_interval; // unit: bits
+                self.cluster.set_now(now);
+                let type_loads = self.policy.type_loads(&self.cluster);
+                let busy_nodes = type_loads.iter().map(|x| x.busy_nodes).sum::<usize>();
+                let traffic = self.policy.traffic(&self.cluster);
+                counters.avg_busy_nodes += busy_nodes as f64 * stat_interval; // unit: s
+                counters.max_busy_nodes = usize::max(counters.max_busy_nodes, busy_nodes);
+                for (node_type, type_load) in type_loads.iter().enumerate() {
+                    counters.node_energy +=
+                        crate::energy::type_power(&self.config.node_types[node_type], type_load)
+                            * stat_interval;
+                    counters.avg_cpu_used += type_load.used.cpu as f64 * stat_interval;
+                    counters.avg_memory_used += type_load.used.memory as f64 * stat_interval;
+                    counters.avg_busy_nodes_per_type[node_type] +=
+                        type_load.busy_nodes as f64 * stat_interval;
+                    counters.max_busy_nodes_per_type[node_type] = usize::max(
+                        counters.max_busy_nodes_per_type[node_type],
+                        type_load.busy_nodes,
+                    );
+                }
+                counters.total_traffic += traffic * self.config.job_invocation_rate * stat_interval; // unit: bits
+                counters.migration_node_seconds +=
+                    self.cluster.migrating_nodes() as f64 * stat_interval;
+                let fragmentation_index =
+                    crate::defragmentation::fragmentation_index(&self.cluster, &type_loads);
+                counters.avg_fragmentation_index += fragmentation_index * stat_interval;
                 match event {
                     Event::JobStart(_) => {
-                        // create a new job and draw randomly its lifetime
-                        let job = self.job_factory.make();
-                        let job_lifetime =
-                            job_duration_rv.sample(&mut self.job_lifetime_rng).ceil() as u64;
+                        // create a new job, or draw it from the catalog, and draw
+                        // randomly its lifetime or, with batch execution, the
+                        // durations of its tasks, unless replaying the next job
+                        // of a trace
+                        let (job, job_lifetime, durations) = match &mut self.replay {
+                            Some(replay) => {
+                                let trace_job = replay.pop_front().unwrap();
+                                (trace_job.job, trace_job.lifetime, trace_job.durations)
+                            }
+                            None => {
+                                let job = match &mut self.job_catalog {
+                                    Some(job_catalog) => job_catalog.make(),
+                                    None => self.job_factory.make(),
+                                };
+                                let durations = match &mut self.task_duration_rv {
+                                    Some(rv) => (0..job.graph.node_count())
+                                        .map(|_| rv.sample())
+                                        .collect::<Vec<f64>>(),
+                                    None => vec![],
+                                };
+                                let job_lifetime =
+                                    job_duration_rv.sample(&mut self.job_lifetime_rng);
+                                (job, job_lifetime, durations)
+                            }
+                        };
+                        let durations = match self.config.job_execution {
+                            crate::job::JobExecution::Pipeline => None,
+                            crate::job::JobExecution::Batch => Some(durations),
+                        };
+                        let job_lifetime = match &durations {
+                            None => job_lifetime,
+                            Some(durations) => {
+                                job.finish_times(durations).into_iter().fold(0.0, f64::max)
+                            }
+                        };
                         log::debug!(
                             "A {} job ID {} (lifetime {} s) {}",
                             now,
@@ -253,322 +671,505 @@ impl Simulation {
                         );
 
                         // add it to the set of active jobs
-                        let _insert_ret = self.active_jobs.insert(job_id, job.clone());
-                        assert!(_insert_ret.is_none());
+                        self.cluster.insert_job(job_id, job.clone());
+                        self.cluster.set_job_end(job_id, now + job_lifetime);
 
-                        // allocate the tasks of a job to processing nodes
-                        self.allocate(job_id, &job);
+                        // allocate the tasks of a job to processing nodes, unless
+                        // they are allocated only when they start
+                        if durations.is_none() {
+                            self.policy.allocate(&mut self.cluster, job_id, &job);
+                        }
+                        if self.trace.enabled() {
+                            self.trace.record(&crate::trace::Record::JobArrival {
+                                time: now,
+                                job_id,
+                                lifetime: job_lifetime,
+                                num_tasks: job.graph.node_count(),
+                                num_edges: job.graph.edge_count(),
+                                total_cpu: job.total_cpu(),
+                                total_state_size: job.total_state_size(),
+                                total_arg_size: job.total_arg_size(),
+                            });
+                            for task_id in 0..job.graph.node_count() as u32 {
+                                // stateless policies do not place tasks on nodes
+                                if let Some(node_id) = self.cluster.allocation(job_id, task_id) {
+                                    self.trace.record(&crate::trace::Record::TaskPlacement {
+                                        time: now,
+                                        job_id,
+                                        task_id,
+                                        node_id,
+                                    });
+                                }
+                            }
+                        }
 
-                        // schedule the end of this job
-                        events.push(Event::JobEnd(now + job_lifetime, job_id));
+                        match durations {
+                            // schedule the end of this job
+                            None => events.push(Event::JobEnd(now + job_lifetime, job_id)),
 
-                        // schedule a new job
+                            // start the tasks that do not depend on other tasks
+                            Some(durations) => {
+                                let pending = job
+                                    .graph
+                                    .node_indices()
+                                    .map(|x| {
+                                        job.graph
+                                            .neighbors_directed(x, petgraph::Direction::Incoming)
+                                            .count()
+                                    })
+                                    .collect::<Vec<usize>>();
+                                for (task_id, _) in
+                                    pending.iter().enumerate().filter(|(_, x)| **x == 0)
+                                {
+                                    events.push(Event::TaskStart(now, job_id, task_id as u32));
+                                }
+                                batch_jobs.insert(
+                                    job_id,
+                                    BatchJob {
+                                        remaining: durations.len(),
+                                        durations,
+                                        pending,
+                                    },
+                                );
+                            }
+                        }
+
+                        // schedule a new job, if any
                         job_id += 1;
-                        events.push(Event::JobStart(
-                            now + job_interarrival_rv
-                                .sample(&mut self.job_interarrival_rng)
-                                .ceil() as u64,
-                        ));
+                        match &self.replay {
+                            Some(replay) => {
+                                if let Some(trace_job) = replay.front() {
+                                    events.push(Event::JobStart(trace_job.arrival));
+                                }
+                            }
+                            None => events.push(Event::JobStart(self.arrival_process.next(now))),
+                        }
                     }
                     Event::JobEnd(_, id) => {
                         log::debug!("T {} job ID {}", now, id);
-                        self.deallocate(id);
+                        self.trace.record(&crate::trace::Record::JobDeparture {
+                            time: now,
+                            job_id: id,
+                        });
+                        self.policy.deallocate(&mut self.cluster, id);
+                        self.cluster.remove_job(id);
+                    }
+                    Event::TaskStart(_, id, task_id) => {
+                        log::debug!("S {} job ID {} task ID {}", now, id, task_id);
+                        let job = self.cluster.job(id).unwrap().clone();
+                        self.policy
+                            .allocate_tasks(&mut self.cluster, id, &job, &[task_id]);
+                        // stateless policies do not place tasks on nodes
+                        if let Some(node_id) = self.cluster.allocation(id, task_id) {
+                            self.trace.record(&crate::trace::Record::TaskPlacement {
+                                time: now,
+                                job_id: id,
+                                task_id,
+                                node_id,
+                            });
+                        }
+                        running_tasks += 1;
+
+                        // schedule the end of this task
+                        let duration = batch_jobs[&id].durations[task_id as usize];
+                        events.push(Event::TaskEnd(now + duration, id, task_id));
+                    }
+                    Event::TaskEnd(_, id, task_id) => {
+                        log::debug!("F {} job ID {} task ID {}", now, id, task_id);
+                        self.trace.record(&crate::trace::Record::TaskCompletion {
+                            time: now,
+                            job_id: id,
+                            task_id,
+                        });
+                        self.policy
+                            .deallocate_tasks(&mut self.cluster, id, &[task_id]);
+                        running_tasks -= 1;
+
+                        // start the tasks whose predecessors have all finished
+                        let batch_job = batch_jobs.get_mut(&id).unwrap();
+                        let job = self.cluster.job(id).unwrap();
+                        for successor in job
+                            .graph
+                            .neighbors_directed(task_id.into(), petgraph::Direction::Outgoing)
+                        {
+                            batch_job.pending[successor.index()] -= 1;
+                            if batch_job.pending[successor.index()] == 0 {
+                                events.push(Event::TaskStart(now, id, successor.index() as u32));
+                            }
+                        }
+
+                        // the job ends with its last task
+                        batch_job.remaining -= 1;
+                        if batch_job.remaining == 0 {
+                            log::debug!("T {} job ID {}", now, id);
+                            self.trace.record(&crate::trace::Record::JobDeparture {
+                                time: now,
+                                job_id: id,
+                            });
+                            self.cluster.remove_job(id);
+                            batch_jobs.remove(&id);
+                        }
+                    }
+                    Event::MigrationEnd(_, node_id, demand) => {
+                        log::debug!("N {} node ID {} {}", now, node_id, demand);
+                        self.cluster.release(node_id, demand);
+                        migrations_in_progress -= 1;
+                    }
+                    Event::Sample(_) => {
+                        samples.push(Sample {
+                            time: now,
+                            busy_nodes,
+                            active_jobs: self.cluster.active_jobs().len(),
+                            active_tasks: match self.config.job_execution {
+                                crate::job::JobExecution::Pipeline => self
+                                    .cluster
+                                    .active_jobs()
+                                    .values()
+                                    .map(|x| x.graph.node_count())
+                                    .sum(),
+                                crate::job::JobExecution::Batch => running_tasks,
+                            },
+                            traffic_rate: traffic * self.config.job_invocation_rate,
+                            cpu_used: type_loads.iter().map(|x| x.used.cpu).sum(),
+                            memory_used: type_loads.iter().map(|x| x.used.memory).sum(),
+                        });
+                        checkpoints.push((now, counters.checkpoint()));
+                        events.push(Event::Sample(now + self.config.sampling_interval.unwrap()));
+                    }
+                    Event::WarmUpEnd(_) => {
+                        log::debug!("W {}", now);
+                        checkpoints.push((now, counters.checkpoint()));
                     }
                     Event::ExperimentEnd(_) => {
                         log::debug!("E {}", now);
                         break 'main_loop;
                     }
                     Event::Defragmentation(_) => {
+                        if let Some(threshold) = self.config.defragmentation_threshold {
+                            // the fragmentation may have decreased since scheduled
+                            defragmentation_pending = false;
+                            if fragmentation_index <= threshold {
+                                continue;
+                            }
+                            next_defragmentation = now + self.config.defragmentation_interval;
+                        }
                         log::debug!("D {}", now);
 
-                        // perform optimization of the current active jobs
-                        let (migration_traffic, num_migrations) = self.defragment();
-                        total_traffic += migration_traffic as f64;
-                        migration_rate += num_migrations;
+                        // perform optimization of the current active jobs, unless
+                        // the migrations of the previous round are still in progress
+                        if migrations_in_progress > 0 {
+                            log::debug!("D {} skipped: migrations in progress", now);
+                            if self.config.defragmentation_threshold.is_none() {
+                                events.push(Event::Defragmentation(
+                                    now + self.config.defragmentation_interval,
+                                ));
+                            }
+                            continue;
+                        }
+                        let old_allocations = self.cluster.task_allocations();
+                        let (migration_traffic, num_migrations) = self
+                            .policy
+                            .defragment(&mut self.cluster, &self.defragmentation);
+
+                        let busy_nodes_after = self.busy_nodes();
+                        self.trace.record(&crate::trace::Record::Defragmentation {
+                            time: now,
+                            fragmentation_index,
+                            busy_nodes_before: busy_nodes,
+                            busy_nodes_after,
+                            num_migrations,
+                            migration_traffic,
+                        });
+
+                        // the state of the migrated tasks is transferred in parallel
+                        if self.config.migration_bandwidth.is_some() || self.trace.enabled() {
+                            for (job_id, task_id, old_node) in old_allocations {
+                                let new_node = self.cluster.allocation(job_id, task_id).unwrap();
+                                if new_node == old_node {
+                                    continue;
+                                }
+                                let demand = self
+                                    .cluster
+                                    .job(job_id)
+                                    .unwrap()
+                                    .graph
+                                    .node_weight(task_id.into())
+                                    .unwrap()
+                                    .demand();
+                                let duration = self
+                                    .config
+                                    .migration_bandwidth
+                                    .map(|bandwidth| demand.memory as f64 / bandwidth);
+                                self.trace.record(&crate::trace::Record::Migration {
+                                    time: now,
+                                    job_id,
+                                    task_id,
+                                    src_node_id: old_node,
+                                    dst_node_id: new_node,
+                                    state_size: demand.memory,
+                                    duration,
+                                });
+                                // the source resources are reserved right away,
+                                // before any other event at the same time
+                                if let Some(duration) = duration {
+                                    log::debug!("M {} node ID {} {}", now, old_node, demand);
+                                    self.cluster.reserve(old_node, demand);
+                                    events.push(Event::MigrationEnd(
+                                        now + duration,
+                                        old_node,
+                                        demand,
+                                    ));
+                                    migrations_in_progress += 1;
+                                }
+                            }
+                        }
+                        counters.total_traffic += migration_traffic as f64;
+                        counters.migration_rate += num_migrations;
+                        counters.freed_nodes += busy_nodes as i64 - busy_nodes_after as i64;
+                        counters.num_defragmentations += 1;
 
                         // schedule the next defragmentation
-                        events.push(Event::Defragmentation(
-                            now + self.config.defragmentation_interval,
-                        ));
+                        if self.config.defragmentation_threshold.is_none() {
+                            events.push(Event::Defragmentation(
+                                now + self.config.defragmentation_interval,
+                            ));
+                        }
+                    }
+                }
+
+                // schedule a defragmentation if the fragmentation index
+                // has exceeded the threshold
+                if let Some(threshold) = self.config.defragmentation_threshold {
+                    if defragmentation_enabled
+                        && !defragmentation_pending
+                        && crate::defragmentation::fragmentation_index(
+                            &self.cluster,
+                            &self.policy.type_loads(&self.cluster),
+                        ) > threshold
+                    {
+                        events.push(Event::Defragmentation(f64::max(now, next_defragmentation)));
+                        defragmentation_pending = true;
                     }
                 }
             }
         }
         let execution_time = real_now.elapsed().as_secs_f64();
+        self.trace.flush();
+
+        // discard the metrics collected during the warm-up period
+        let warm_up = match self.config.warm_up {
+            WarmUp::None => 0.0,
+            WarmUp::Fixed(warm_up) => warm_up,
+            WarmUp::Mser5 => {
+                let series = samples
+                    .iter()
+                    .map(|x| x.busy_nodes as f64)
+                    .collect::<Vec<f64>>();
+                match crate::warm_up::mser5(&series) {
+                    0 => 0.0,
+                    truncation => samples[truncation - 1].time,
+                }
+            }
+        };
+        let checkpoints = checkpoints
+            .into_iter()
+            .filter(|(time, _)| *time >= warm_up)
+            .map(|(_, counters)| counters)
+            .collect::<Vec<Counters>>();
+        let Counters {
+            mut avg_busy_nodes,
+            max_busy_nodes,
+            mut avg_busy_nodes_per_type,
+            max_busy_nodes_per_type,
+            avg_cpu_used,
+            avg_memory_used,
+            mut node_energy,
+            total_traffic,
+            migration_rate,
+            freed_nodes,
+            num_defragmentations,
+            avg_fragmentation_index,
+            migration_node_seconds,
+        } = counters.since(&checkpoints);
 
         // adapt the busy node metric to the different policies
-        avg_busy_nodes = match self.config.policy {
-            Policy::StatelessMinNodes | Policy::StatefulBestFit | Policy::StatefulRandom => {
-                avg_busy_nodes / self.config.duration as f64
+        let duration = self.config.duration - warm_up;
+
+        // with peak provisioning, the nodes that are not busy remain idle
+        if self.policy.peak_provisioning() {
+            for (node_type, busy_nodes) in avg_busy_nodes_per_type.iter().enumerate() {
+                node_energy += self.config.node_types[node_type].idle_power
+                    * (max_busy_nodes_per_type[node_type] as f64 * duration - busy_nodes);
             }
-            Policy::StatelessMaxBalancing => max_busy_nodes as f64,
+        }
+
+        avg_busy_nodes = match self.policy.peak_provisioning() {
+            false => avg_busy_nodes / duration,
+            true => max_busy_nodes as f64,
+        };
+        for (node_type, busy_nodes) in avg_busy_nodes_per_type.iter_mut().enumerate() {
+            *busy_nodes = match self.policy.peak_provisioning() {
+                false => *busy_nodes / duration,
+                true => max_busy_nodes_per_type[node_type] as f64,
+            };
+        }
+
+        // utilization of the resources of the busy nodes, in each dimension
+        let node_types = &self.config.node_types;
+        let busy_capacity = |capacity: fn(&crate::cluster::Resources) -> usize| {
+            (0..num_node_types)
+                .map(|x| avg_busy_nodes_per_type[x] * capacity(&node_types[x].capacity) as f64)
+                .sum::<f64>()
+        };
+        let utilization = |avg_used: f64, busy_capacity: f64| match busy_capacity > 0.0 {
+            true => avg_used / duration / busy_capacity,
+            false => 0.0,
         };
+        let cpu_utilization = utilization(avg_cpu_used, busy_capacity(|x| x.cpu));
+        let memory_utilization = utilization(avg_memory_used, busy_capacity(|x| x.memory));
+
+        // cost of the busy nodes
+        let cost = (0..num_node_types)
+            .map(|x| avg_busy_nodes_per_type[x] * duration / 3600.0 * node_types[x].hourly_price)
+            .sum::<f64>();
 
         // return the simulation output
         Output {
             avg_busy_nodes,
             total_traffic,
             seed: self.config.seed,
-            migration_rate: migration_rate as f64 / self.config.duration as f64,
+            num_jobs: job_id,
+            warm_up,
+            migration_rate: migration_rate as f64 / duration,
+            avg_freed_nodes: match num_defragmentations {
+                0 => 0.0,
+                _ => freed_nodes as f64 / num_defragmentations as f64,
+            },
+            repacking_order: self.config.repacking_order,
+            defragmentation_rounds: num_defragmentations,
+            avg_fragmentation_index: avg_fragmentation_index / duration,
+            migration_node_seconds,
+            cpu_utilization,
+            memory_utilization,
+            avg_busy_nodes_per_type: node_types
+                .iter()
+                .zip(avg_busy_nodes_per_type.iter())
+                .map(|(node_type, busy_nodes)| (node_type.name.clone(), *busy_nodes))
+                .collect(),
+            cost,
+            node_energy: crate::energy::to_kwh(node_energy),
+            network_energy: crate::energy::to_kwh(crate::energy::network_energy(
+                total_traffic,
+                self.config.network_energy,
+            )),
             execution_time,
+            samples,
         }
     }
+}
 
-    fn job_task_hash(job_id: u64, task_id: u32) -> u64 {
-        assert!(task_id < 1000);
-        job_id * 1000 + task_id as u64
-    }
-
-    fn allocate(&mut self, job_id: u64, job: &crate::job::Job) {
-        match self.config.policy {
-            Policy::StatelessMinNodes | Policy::StatelessMaxBalancing => {}
-            Policy::StatefulBestFit => {
-                'allocation_loop: for (index, weight) in job.graph.node_references() {
-                    let task_id = index.index() as u32;
-                    let cpu = weight.cpu_request;
-                    assert!(cpu <= self.config.node_capacity);
-
-                    // if there is a node hosting a task which is a predecessor of this
-                    // node with enough residual capacity to host this task too, then
-                    // use it
-                    for pred_task_id in job.graph.neighbors_directed(index, petgraph::Incoming) {
-                        if let Some(pred_node_id) = self.allocations.get(
-                            &Simulation::job_task_hash(job_id, pred_task_id.index() as u32),
-                        ) {
-                            let pred_node = &self.nodes[*pred_node_id];
-                            if self.capacity_residual(pred_node, cpu).is_some() {
-                                self.add_job(job_id, task_id, *pred_node_id);
-                                continue 'allocation_loop;
-                            }
-                        }
-                    }
-
-                    // find the active node that would leaves the smallest residual
-                    // if this task is assigned to it
-                    let mut candidates = vec![];
-                    match self
-                        .nodes
-                        .iter()
-                        .filter_map(|x| self.capacity_residual(x, cpu))
-                        .min()
-                    {
-                        None => {
-                            // there is no node (active or inactive) where this new task would fit
-                        }
-                        Some(min_residual) => {
-                            // there is at least one node where the new task would fit, leaving
-                            // min_residual as residual capacity, including both active and
-                            // inactive nodes
-                            //
-                            // note that an inactive node will be selected below only if there
-                            // are no active nodes that could fulfill the request, with no need
-                            // of filtering on this condition explicitly, because we pick the
-                            // node that leaves the smallest residual
-                            for (node_id, node) in self.nodes.iter().enumerate() {
-                                if let Some(residual) = self.capacity_residual(node, cpu) {
-                                    if residual == min_residual {
-                                        candidates.push(node_id);
-                                    }
-                                }
-                            }
-                        }
-                    }
-                    match candidates.choose(&mut self.allocate_rng) {
-                        Some(node_id) => {
-                            self.add_job(job_id, task_id, *node_id);
-                        }
-                        None => {
-                            self.nodes.push(Node { jobs: vec![] });
-                            self.add_job(job_id, task_id, self.nodes.len() - 1);
-                        }
-                    }
-                }
-            }
-            Policy::StatefulRandom => {
-                for (index, weight) in job.graph.node_references() {
-                    let task_id = index.index() as u32;
-                    let cpu = weight.cpu_request;
-                    assert!(cpu <= self.config.node_capacity);
-                    let mut candidates = vec![];
-                    for (node_id, node) in self.nodes.iter().enumerate() {
-                        if self.capacity_residual(node, cpu).is_some() {
-                            candidates.push(node_id);
-                        }
-                    }
-                    match candidates.choose(&mut self.allocate_rng) {
-                        Some(node_id) => {
-                            self.add_job(job_id, task_id, *node_id);
-                        }
-                        None => match self
-                            .nodes
-                            .iter()
-                            .enumerate()
-                            .find(|(_node_id, node)| !node.is_active())
-                        {
-                            Some((node_id, _node)) => {
-                                self.add_job(job_id, task_id, node_id);
-                            }
-                            None => {
-                                self.nodes.push(Node { jobs: vec![] });
-                                self.add_job(job_id, task_id, self.nodes.len() - 1);
-                            }
-                        },
-                    }
-                }
-            }
-        };
-    }
-
-    fn deallocate(&mut self, job_id: u64) {
-        match self.config.policy {
-            Policy::StatelessMinNodes | Policy::StatelessMaxBalancing => {}
-            Policy::StatefulRandom | Policy::StatefulBestFit => {
-                self.active_jobs
-                    .get(&job_id)
-                    .unwrap()
-                    .graph
-                    .node_indices()
-                    .for_each(|x| self.del_job(job_id, x.index() as u32));
-            }
-        };
-        let _remove_ret = self.active_jobs.remove(&job_id);
-        assert!(_remove_ret.is_some());
-    }
-
-    fn add_job(&mut self, job_id: u64, task_id: u32, node_id: usize) {
-        log::debug!("add job {}, task {}, to node {}", job_id, task_id, node_id);
-        self.nodes[node_id].jobs.push((job_id, task_id));
-        self.allocations
-            .insert(Simulation::job_task_hash(job_id, task_id), node_id);
-    }
-
-    fn del_job(&mut self, job_id: u64, task_id: u32) {
-        let node_id = self
-            .allocations
-            .remove(&Simulation::job_task_hash(job_id, task_id))
-            .unwrap();
-        log::debug!(
-            "del job {}, task {}, from node {}",
-            job_id,
-            task_id,
-            node_id
-        );
-        self.nodes[node_id]
-            .jobs
-            .retain(|(cur_job_id, cur_task_id)| *cur_job_id != job_id || *cur_task_id != task_id);
-    }
-
-    fn capacity_used(&self, node: &Node) -> usize {
-        node.jobs
-            .iter()
-            .map(|(job_id, task_id)| {
-                self.active_jobs
-                    .get(job_id)
-                    .unwrap()
-                    .graph
-                    .node_weight((*task_id).into())
-                    .unwrap()
-                    .cpu_request
-            })
-            .sum::<usize>()
-    }
-
-    /// Return the capacity residual if this node was allocated
-    /// a new task with given capacity, or `None` if the new
-    /// task would not fit into the node.
-    fn capacity_residual(&self, node: &Node, new_capacity: usize) -> Option<usize> {
-        let new_capacity_used = self.capacity_used(node) + new_capacity;
-        if self.config.node_capacity >= new_capacity_used {
-            Some(self.config.node_capacity - new_capacity_used)
-        } else {
-            None
-        }
-    }
-
-    fn defragment(&mut self) -> (usize, usize) {
-        match self.config.policy {
-            Policy::StatelessMinNodes | Policy::StatelessMaxBalancing => (0, 0),
-            Policy::StatefulBestFit => {
-                // save the previous allocation to compute the number of migrations
-                // and the traffic due to the migration of task state
-                let old_allocations = std::mem::take(&mut self.allocations);
-                assert!(self.allocations.is_empty());
-                self.nodes = vec![];
-                let mut migration_traffic = 0;
-                let mut num_migrations = 0;
-                for (job_id, job) in self.active_jobs.clone().into_iter() {
-                    self.allocate(job_id, &job);
-                    for (task_id, weight) in job.graph.node_references() {
-                        let allocation_key =
-                            Simulation::job_task_hash(job_id, task_id.index() as u32);
-                        let old_node = old_allocations.get(&allocation_key).unwrap();
-                        let new_node = self.allocations.get(&allocation_key).unwrap();
-                        if old_node != new_node {
-                            num_migrations += 1;
-                            migration_traffic += weight.state_size;
-                        }
-                    }
+/// Run the simulations with the given configurations on a pool of
+/// threads and return their outputs, in the same order.
+///
+/// Fail if any simulation cannot be created, in which case the error
+/// of the first such configuration is returned.
+pub fn run_batch(configs: Vec<Config>, concurrency: usize) -> anyhow::Result<Vec<Output>> {
+    anyhow::ensure!(concurrency > 0, "vanishing concurrency");
+    let num_configs = configs.len();
+    let queue = std::sync::Mutex::new(configs.into_iter().enumerate());
+    let (tx, rx) = std::sync::mpsc::channel();
+    std::thread::scope(|scope| {
+        for i in 0..usize::min(concurrency, num_configs) {
+            let tx = tx.clone();
+            let queue = &queue;
+            scope.spawn(move || {
+                log::info!("spawned worker #{}", i);
+                loop {
+                    let next = queue.lock().unwrap().next();
+                    let Some((index, config)) = next else {
+                        break;
+                    };
+                    let seed = config.seed;
+                    let output = Simulation::new(config)
+                        .map(|mut sim| sim.run())
+                        .map_err(|err| {
+                            anyhow::anyhow!("invalid simulation with seed {}: {}", seed, err)
+                        });
+                    tx.send((index, output)).unwrap();
                 }
-                assert!(self.allocations.len() == old_allocations.len());
-                (migration_traffic, num_migrations)
-            }
-            Policy::StatefulRandom => (0, 0),
+                log::info!("terminated worker #{}", i);
+            });
         }
-    }
+    });
+    drop(tx);
 
-    /// Return the statistics computed at this time: (number of busy nodes, total traffic).
-    fn compute_stats(&mut self, node_capacity: usize) -> (usize, f64) {
-        let busy_nodes = |x: &std::collections::HashMap<u64, crate::job::Job>| {
-            (x.values().map(|x| x.total_cpu()).sum::<usize>() as f64 / node_capacity as f64).ceil()
-                as usize
-        };
-        let tot_size = |x: &std::collections::HashMap<u64, crate::job::Job>| {
-            x.values().map(|x| x.total_state_size()).sum::<usize>() as f64
-                + x.values().map(|x| x.total_arg_size()).sum::<usize>() as f64
-        };
-
-        match self.config.policy {
-            Policy::StatelessMinNodes | Policy::StatelessMaxBalancing => {
-                (busy_nodes(&self.active_jobs), tot_size(&self.active_jobs))
-            }
-            Policy::StatefulBestFit | Policy::StatefulRandom => (
-                self.nodes.iter().filter(|x| x.is_active()).count(),
-                self.active_jobs
-                    .iter()
-                    .map(|(job_id, job)| {
-                        let mut cnt = 0;
-                        for node_ndx in job.graph.node_indices() {
-                            for edge in job.graph.edges(node_ndx) {
-                                let u = self
-                                    .allocations
-                                    .get(&Simulation::job_task_hash(
-                                        *job_id,
-                                        edge.source().index() as u32,
-                                    ))
-                                    .unwrap();
-                                let v = self
-                                    .allocations
-                                    .get(&Simulation::job_task_hash(
-                                        *job_id,
-                                        edge.target().index() as u32,
-                                    ))
-                                    .unwrap();
-                                if u != v {
-                                    cnt += edge.weight().arg_size;
-                                }
-                            }
-                        }
-                        cnt
-                    })
-                    .sum::<usize>() as f64,
-            ),
-        }
+    let mut outputs = (0..num_configs)
+        .map(|_| None)
+        .collect::<Vec<Option<anyhow::Result<Output>>>>();
+    for (index, output) in rx {
+        outputs[index] = Some(output);
     }
+    outputs.into_iter().map(|x| x.unwrap()).collect()
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::arrival::{ArrivalProfile, InterarrivalDistribution};
+    use crate::cluster::{NodeType, Resources};
+    use crate::defragmentation::{
+        DefragmentationMode, MigrationBudget, RemainingLifetime, RepackingOrder,
+    };
+    use crate::job::JobExecution;
+
+    fn default_node_type() -> NodeType {
+        NodeType::new("default", Resources::new(1000, 1048576), 50.0, 100.0, 1.0)
+    }
+
+    /// Return the configuration shared by the tests, which override
+    /// only the fields of interest.
+    fn test_config() -> Config {
+        Config {
+            duration: 3600.0,
+            job_lifetime: 10.0,
+            job_execution: JobExecution::Pipeline,
+            job_interarrival: 1.0,
+            arrival_profile: ArrivalProfile::Constant,
+            interarrival_distribution: InterarrivalDistribution::Exponential,
+            job_invocation_rate: 5.0,
+            node_types: vec![default_node_type()],
+            network_energy: 0.4,
+            defragmentation_interval: 60.0,
+            defragmentation_threshold: None,
+            defragmentation_mode: DefragmentationMode::None,
+            repacking_order: RepackingOrder::Arrival,
+            migration_bandwidth: None,
+            migration_budget: MigrationBudget::default(),
+            remaining_lifetime: RemainingLifetime::Oracle,
+            policy: Policy::from("stateful-best-fit").unwrap(),
+            state_mul: 100.0,
+            arg_mul: 100.0,
+            seed: 42,
+            warm_up: WarmUp::None,
+            sampling_interval: None,
+            trace: None,
+            alibaba_trace: None,
+            job_catalog: None,
+        }
+    }
+
+    /// Return the jobs of the sample of the Alibaba trace, with the size
+    /// multipliers of [`test_config`].
+    fn sample_trace() -> anyhow::Result<std::sync::Arc<Vec<crate::alibaba::TraceJob>>> {
+        let config = test_config();
+        Ok(std::sync::Arc::new(crate::alibaba::read_batch_tasks(
+            "data/batch_task_sample.csv",
+            config.state_mul,
+            config.arg_mul,
+            false,
+            &config.node_types,
+        )?))
+    }
 
     #[test]
     fn test_simulation_run() -> anyhow::Result<()> {
@@ -577,16 +1178,31 @@ mod tests {
             let mut out = vec![];
             for i in 1..4 {
                 let mut sim = Simulation::new(Config {
-                    duration: 3600 * i,
+                    duration: 3600.0 * i as f64,
                     job_lifetime: 10.0,
+                    job_execution: JobExecution::Pipeline,
                     job_interarrival: 1.0,
+                    arrival_profile: ArrivalProfile::Constant,
+                    interarrival_distribution: InterarrivalDistribution::Exponential,
                     job_invocation_rate: 5.0,
-                    node_capacity: 1000,
-                    defragmentation_interval: 300,
+                    node_types: vec![default_node_type()],
+                    network_energy: 0.4,
+                    defragmentation_interval: 300.0,
+                    defragmentation_threshold: None,
+                    defragmentation_mode: DefragmentationMode::Full,
+                    repacking_order: RepackingOrder::Arrival,
+                    migration_bandwidth: None,
+                    migration_budget: MigrationBudget::default(),
+                    remaining_lifetime: RemainingLifetime::Oracle,
                     policy: policy.clone(),
                     state_mul: 100.0,
                     arg_mul: 100.0,
                     seed: 42,
+                    warm_up: WarmUp::None,
+                    sampling_interval: None,
+                    trace: None,
+                    alibaba_trace: None,
+                    job_catalog: None,
                 })?;
                 out.push(sim.run());
             }
@@ -601,4 +1217,890 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn test_simulation_graph_partitioning() -> anyhow::Result<()> {
+        let mut out = vec![];
+        for policy in ["stateful-best-fit", "stateful-graph-partitioning"] {
+            let mut sim = Simulation::new(Config {
+                defragmentation_interval: 3600.0,
+                defragmentation_mode: DefragmentationMode::Full,
+                policy: Policy::from(policy)?,
+                ..test_config()
+            })?;
+            out.push(sim.run());
+        }
+        println!("{:?}", out);
+        assert!(out[1].total_traffic < out[0].total_traffic);
+        assert!(out[1].avg_busy_nodes < out[0].avg_busy_nodes * 1.1);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_simulation_memory() -> anyhow::Result<()> {
+        let mut out = vec![];
+        for node_memory in [1048576, 400] {
+            let mut sim = Simulation::new(Config {
+                node_types: vec![NodeType::new(
+                    "default",
+                    Resources::new(1000, node_memory),
+                    50.0,
+                    100.0,
+                    1.0,
+                )],
+                defragmentation_interval: 300.0,
+                defragmentation_mode: DefragmentationMode::Full,
+                ..test_config()
+            })?;
+            out.push(sim.run());
+        }
+        println!("{:?}", out);
+        assert!(out[1].avg_busy_nodes > out[0].avg_busy_nodes);
+        assert!(out[1].cpu_utilization < out[0].cpu_utilization);
+        assert!(out[1].memory_utilization > out[0].memory_utilization);
+        for output in out {
+            assert!(output.cpu_utilization > 0.0 && output.cpu_utilization <= 1.0);
+            assert!(output.memory_utilization > 0.0 && output.memory_utilization <= 1.0);
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_simulation_node_types() -> anyhow::Result<()> {
+        for policy in Policy::all() {
+            let mut sim = Simulation::new(Config {
+                node_types: NodeType::from_file("data/node_types.csv")?,
+                defragmentation_interval: 300.0,
+                defragmentation_mode: DefragmentationMode::Full,
+                policy: policy.clone(),
+                ..test_config()
+            })?;
+            let out = sim.run();
+            println!("{} {}", policy, out);
+            assert_eq!(3, out.avg_busy_nodes_per_type.len());
+            let sum = out
+                .avg_busy_nodes_per_type
+                .iter()
+                .map(|(_, busy_nodes)| *busy_nodes)
+                .sum::<f64>();
+            match sim.policy.peak_provisioning() {
+                false => assert!((sum - out.avg_busy_nodes).abs() < 1e-6),
+                true => assert!(sum >= out.avg_busy_nodes),
+            }
+            assert!(out.cost > 0.0);
+            assert!(out.cpu_utilization > 0.0 && out.cpu_utilization <= 1.0);
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_simulation_energy() -> anyhow::Result<()> {
+        for policy in Policy::all() {
+            let mut sim = Simulation::new(Config {
+                defragmentation_interval: 300.0,
+                defragmentation_mode: DefragmentationMode::Full,
+                policy: policy.clone(),
+                ..test_config()
+            })?;
+            let out = sim.run();
+            println!("{} {}", policy, out);
+
+            // the power of a busy node is between 50 W (idle) and 100 W (peak)
+            let busy_node_energy = out.avg_busy_nodes * 3600.0 / 3.6e6;
+            assert!(out.node_energy > 50.0 * busy_node_energy);
+            assert!(out.node_energy < 100.0 * busy_node_energy);
+            assert!((out.network_energy - out.total_traffic * 0.4 / 3.6e6).abs() < 1e-9);
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_simulation_incremental_defragmentation() -> anyhow::Result<()> {
+        let mut out = vec![];
+        for (defragmentation_mode, tasks) in [
+            (DefragmentationMode::Full, None),
+            (DefragmentationMode::Incremental, None),
+            (DefragmentationMode::Incremental, Some(5)),
+        ] {
+            let mut sim = Simulation::new(Config {
+                defragmentation_mode,
+                migration_budget: MigrationBudget { tasks, state: None },
+                ..test_config()
+            })?;
+            out.push(sim.run());
+        }
+        println!("{:?}", out);
+        assert!(out[1].migration_rate < out[0].migration_rate);
+        assert!(out[2].migration_rate <= out[1].migration_rate);
+        assert!(out[2].migration_rate <= 5.0 / 60.0);
+        for output in &out {
+            assert!(output.avg_freed_nodes >= 0.0);
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_simulation_cost_benefit_defragmentation() -> anyhow::Result<()> {
+        let mut out = vec![];
+        for (defragmentation_mode, remaining_lifetime) in [
+            (DefragmentationMode::Incremental, RemainingLifetime::Oracle),
+            (DefragmentationMode::CostBenefit, RemainingLifetime::Oracle),
+            (
+                DefragmentationMode::CostBenefit,
+                RemainingLifetime::Estimated,
+            ),
+        ] {
+            let mut sim = Simulation::new(Config {
+                defragmentation_mode,
+                remaining_lifetime,
+                state_mul: 10000.0,
+                ..test_config()
+            })?;
+            out.push(sim.run());
+        }
+        println!("{:?}", out);
+        for output in &out[1..] {
+            assert!(output.migration_rate < out[0].migration_rate);
+        }
+
+        // the remaining lifetime cannot be estimated without exponential lifetimes
+        for (job_execution, alibaba_trace) in [
+            (JobExecution::Batch, None),
+            (JobExecution::Pipeline, Some(sample_trace()?)),
+        ] {
+            assert!(Simulation::new(Config {
+                job_execution,
+                defragmentation_mode: DefragmentationMode::CostBenefit,
+                remaining_lifetime: RemainingLifetime::Estimated,
+                alibaba_trace,
+                ..test_config()
+            })
+            .is_err());
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_simulation_defragmentation_any_policy() -> anyhow::Result<()> {
+        for policy in Policy::all()
+            .into_iter()
+            .filter(|x| x.name().starts_with("stateful-"))
+        {
+            let mut out = vec![];
+            for defragmentation_mode in [
+                DefragmentationMode::None,
+                DefragmentationMode::Full,
+                DefragmentationMode::Incremental,
+            ] {
+                let mut sim = Simulation::new(Config {
+                    defragmentation_mode,
+                    policy: policy.clone(),
+                    ..test_config()
+                })?;
+                out.push(sim.run());
+            }
+            println!("{} {:?}", policy, out);
+            assert_eq!(0.0, out[0].migration_rate);
+            assert_eq!(0.0, out[0].avg_freed_nodes);
+            assert!(out[1].migration_rate > 0.0);
+            assert!(out[2].migration_rate > 0.0);
+
+            // the other policies already use few nodes without defragmentation
+            if policy.name() == "stateful-random" {
+                assert!(out[2].avg_busy_nodes < out[0].avg_busy_nodes * 0.99);
+            }
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_simulation_threshold_defragmentation() -> anyhow::Result<()> {
+        let mut out = vec![];
+        for defragmentation_threshold in [None, Some(4.0), Some(100.0)] {
+            let mut sim = Simulation::new(Config {
+                defragmentation_threshold,
+                defragmentation_mode: DefragmentationMode::Incremental,
+                policy: Policy::from("stateful-random")?,
+                ..test_config()
+            })?;
+            out.push(sim.run());
+        }
+        println!("{:?}", out);
+        assert!(out[0].defragmentation_rounds >= 59);
+        assert!(out[1].defragmentation_rounds > 0);
+        assert!(out[1].defragmentation_rounds < out[0].defragmentation_rounds);
+        assert_eq!(0, out[2].defragmentation_rounds);
+        assert!(out[2].avg_fragmentation_index > out[0].avg_fragmentation_index);
+        for output in &out {
+            assert!(output.avg_fragmentation_index >= 1.0);
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_simulation_threshold_defragmentation_node_types() -> anyhow::Result<()> {
+        // with heterogeneous node types, the fragmentation index does not
+        // remain above the threshold just because the cheapest nodes are
+        // smaller than the largest ones, so rounds are not triggered at
+        // every interval, and never with stateless policies
+        let mut rounds = vec![];
+        for policy in ["stateful-best-fit", "stateless-min-nodes"] {
+            let out = Simulation::new(Config {
+                node_types: NodeType::from_file("data/node_types.csv")?,
+                defragmentation_threshold: Some(1.5),
+                defragmentation_mode: DefragmentationMode::Full,
+                policy: Policy::from(policy)?,
+                ..test_config()
+            })?
+            .run();
+            println!("{:?}", out);
+            rounds.push(out.defragmentation_rounds);
+        }
+        println!("{:?}", rounds);
+        assert!(rounds[0] > 0);
+        assert!(rounds[0] < 55, "one round per interval: {}", rounds[0]);
+        assert_eq!(0, rounds[1]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_simulation_stateless_node_types_order() -> anyhow::Result<()> {
+        // stateless policies use the type with the lowest price per CPU,
+        // wherever it is in the catalog
+        let mut out = vec![];
+        for reverse in [false, true] {
+            let mut node_types = NodeType::from_file("data/node_types.csv")?;
+            if reverse {
+                node_types.reverse();
+            }
+            let mut output = Simulation::new(Config {
+                node_types,
+                policy: Policy::from("stateless-min-nodes")?,
+                ..test_config()
+            })?
+            .run();
+            output.avg_busy_nodes_per_type.sort_by(|a, b| a.0.cmp(&b.0));
+            out.push(output);
+        }
+        println!("{:?}", out);
+        assert!(out[0].avg_busy_nodes > 0.0);
+        assert_eq!(out[0].avg_busy_nodes, out[1].avg_busy_nodes);
+        assert_eq!(
+            out[0].avg_busy_nodes_per_type,
+            out[1].avg_busy_nodes_per_type
+        );
+        assert_eq!(out[0].cost, out[1].cost);
+        assert_eq!(out[0].node_energy, out[1].node_energy);
+        assert!(out[0]
+            .avg_busy_nodes_per_type
+            .iter()
+            .all(|(name, busy_nodes)| (name == "large") == (*busy_nodes > 0.0)));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_simulation_migration_reservation() -> anyhow::Result<()> {
+        // the jobs arrive at integer times, often together with
+        // defragmentation rounds, and cannot be allocated into resources
+        // still held by the tasks migrating away
+        let filename = std::env::temp_dir().join(format!(
+            "stateful-faas-sim-reservation-{}.jsonl",
+            std::process::id()
+        ));
+        let out = Simulation::new(Config {
+            duration: 1800.0,
+            interarrival_distribution: InterarrivalDistribution::Empirical,
+            defragmentation_mode: DefragmentationMode::Full,
+            migration_bandwidth: Some(100.0),
+            policy: Policy::from("stateful-random")?,
+            trace: Some(filename.to_str().unwrap().to_string()),
+            job_catalog: Some("data/catalog/catalog.csv".to_string()),
+            ..test_config()
+        })?
+        .run();
+        let content = std::fs::read_to_string(&filename)?;
+        std::fs::remove_file(&filename)?;
+        assert!(out.migration_node_seconds > 0.0);
+
+        // replay the trace, with the jobs told apart by their number of tasks
+        let catalog = crate::catalog::JobCatalog::from_file(0, "data/catalog/catalog.csv")?;
+        let capacity = default_node_type().capacity;
+        let demand = |job: &crate::job::Job, task_id: u64| {
+            job.graph
+                .node_weight((task_id as u32).into())
+                .unwrap()
+                .demand()
+        };
+        let mut jobs = std::collections::BTreeMap::new();
+        let mut allocations = std::collections::BTreeMap::new();
+        let mut used = std::collections::BTreeMap::new();
+        let mut reservations = vec![];
+        let mut num_placements = 0;
+        for line in content.lines() {
+            let record = serde_json::from_str::<serde_json::Value>(line)?;
+            let time = record["time"].as_f64().unwrap();
+            let job_id = record["job_id"].as_u64();
+            reservations.retain(|(end, node_id, demand): &(f64, u64, Resources)| {
+                if *end <= time {
+                    *used.get_mut(node_id).unwrap() -= *demand;
+                }
+                *end > time
+            });
+            match record["event"].as_str().unwrap() {
+                "job-arrival" => {
+                    let num_tasks = record["num_tasks"].as_u64().unwrap() as usize;
+                    let job = catalog
+                        .jobs()
+                        .iter()
+                        .find(|x| x.graph.node_count() == num_tasks)
+                        .unwrap();
+                    jobs.insert(job_id.unwrap(), job);
+                }
+                "task-placement" => {
+                    let task_id = record["task_id"].as_u64().unwrap();
+                    let node_id = record["node_id"].as_u64().unwrap();
+                    let demand = demand(jobs[&job_id.unwrap()], task_id);
+                    let node_used = used.entry(node_id).or_insert(Resources::default());
+                    *node_used += demand;
+                    assert!(
+                        node_used.fits_into(&capacity),
+                        "node {} overloaded at {}",
+                        node_id,
+                        time
+                    );
+                    allocations.insert((job_id.unwrap(), task_id), node_id);
+                    num_placements += 1;
+                }
+                "migration" => {
+                    let task_id = record["task_id"].as_u64().unwrap();
+                    let src_node_id = record["src_node_id"].as_u64().unwrap();
+                    let dst_node_id = record["dst_node_id"].as_u64().unwrap();
+                    let duration = record["duration"].as_f64().unwrap();
+                    let demand = demand(jobs[&job_id.unwrap()], task_id);
+                    *used.entry(dst_node_id).or_insert(Resources::default()) += demand;
+                    reservations.push((time + duration, src_node_id, demand));
+                    allocations.insert((job_id.unwrap(), task_id), dst_node_id);
+                }
+                "job-departure" => {
+                    let job = jobs.remove(&job_id.unwrap()).unwrap();
+                    for task_id in 0..job.graph.node_count() as u64 {
+                        let node_id = allocations.remove(&(job_id.unwrap(), task_id)).unwrap();
+                        *used.get_mut(&node_id).unwrap() -= demand(job, task_id);
+                    }
+                }
+                _ => {}
+            }
+        }
+        assert!(num_placements > 0);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_simulation_migration_bandwidth() -> anyhow::Result<()> {
+        let mut out = vec![];
+        for migration_bandwidth in [None, Some(1000.0), Some(10.0)] {
+            let mut sim = Simulation::new(Config {
+                defragmentation_mode: DefragmentationMode::Full,
+                migration_bandwidth,
+                policy: Policy::from("stateful-random")?,
+                ..test_config()
+            })?;
+            out.push(sim.run());
+        }
+        println!("{:?}", out);
+        assert_eq!(0.0, out[0].migration_node_seconds);
+        assert!(out[1].migration_node_seconds > 0.0);
+        assert!(out[2].migration_node_seconds > out[1].migration_node_seconds);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_simulation_arrival_rate() -> anyhow::Result<()> {
+        for job_interarrival in [0.1, 1.0, 2.5] {
+            let duration = 20000.0 * job_interarrival;
+            let mut sim = Simulation::new(Config {
+                duration,
+                job_lifetime: 0.5,
+                job_interarrival,
+                defragmentation_interval: 300.0,
+                policy: Policy::from("stateless-min-nodes")?,
+                ..test_config()
+            })?;
+            let out = sim.run();
+            let measured = duration / out.num_jobs as f64;
+            println!("{} {}", job_interarrival, measured);
+            assert!((measured - job_interarrival).abs() < 0.03 * job_interarrival);
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_simulation_trace() -> anyhow::Result<()> {
+        let filename = std::env::temp_dir().join(format!(
+            "stateful-faas-sim-trace-{}.jsonl",
+            std::process::id()
+        ));
+        let mut sim = Simulation::new(Config {
+            duration: 600.0,
+            defragmentation_mode: DefragmentationMode::Full,
+            policy: Policy::from("stateful-random")?,
+            trace: Some(filename.to_str().unwrap().to_string()),
+            ..test_config()
+        })?;
+        let out = sim.run();
+        let content = std::fs::read_to_string(&filename)?;
+        std::fs::remove_file(&filename)?;
+
+        let mut counts = std::collections::HashMap::new();
+        let mut num_tasks = 0;
+        for line in content.lines() {
+            let record = serde_json::from_str::<serde_json::Value>(line)?;
+            if let Some(value) = record.get("num_tasks") {
+                num_tasks += value.as_u64().unwrap();
+            }
+            *counts
+                .entry(record["event"].as_str().unwrap().to_string())
+                .or_insert(0_u64) += 1;
+        }
+        println!("{:?}", counts);
+        assert_eq!(out.num_jobs, counts["job-arrival"]);
+        assert_eq!(num_tasks, counts["task-placement"]);
+        assert!(counts["job-departure"] > 0);
+        assert!(counts["job-departure"] <= counts["job-arrival"]);
+        assert_eq!(out.defragmentation_rounds as u64, counts["defragmentation"]);
+        assert_eq!(
+            (out.migration_rate * 600.0).round() as u64,
+            counts["migration"]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_simulation_batch_execution() -> anyhow::Result<()> {
+        for (policy, repacking_order) in [
+            ("stateful-best-fit", RepackingOrder::Arrival),
+            ("stateful-graph-partitioning", RepackingOrder::TaskCpu),
+            ("stateless-min-nodes", RepackingOrder::Arrival),
+        ] {
+            let filename = std::env::temp_dir().join(format!(
+                "stateful-faas-sim-batch-{}-{}.jsonl",
+                policy,
+                std::process::id()
+            ));
+            let mut sim = Simulation::new(Config {
+                duration: 600.0,
+                job_execution: JobExecution::Batch,
+                defragmentation_mode: DefragmentationMode::Full,
+                repacking_order,
+                policy: Policy::from(policy)?,
+                sampling_interval: Some(10.0),
+                trace: Some(filename.to_str().unwrap().to_string()),
+                ..test_config()
+            })?;
+            let out = sim.run();
+            let content = std::fs::read_to_string(&filename)?;
+            std::fs::remove_file(&filename)?;
+
+            // a job departs when its last task finishes, after its lifetime
+            let mut arrivals = std::collections::HashMap::new();
+            let mut running = std::collections::HashSet::new();
+            let mut num_completions = std::collections::HashMap::new();
+            let mut num_departures = 0;
+            for line in content.lines() {
+                let record = serde_json::from_str::<serde_json::Value>(line)?;
+                let time = record["time"].as_f64().unwrap();
+                let job_id = record["job_id"].as_u64();
+                match record["event"].as_str().unwrap() {
+                    "job-arrival" => {
+                        let end = time + record["lifetime"].as_f64().unwrap();
+                        let num_tasks = record["num_tasks"].as_u64().unwrap();
+                        arrivals.insert(job_id.unwrap(), (end, num_tasks));
+                    }
+                    "task-placement" => {
+                        assert!(running.insert((job_id.unwrap(), record["task_id"].as_u64())));
+                    }
+                    "task-completion" => {
+                        let task_id = record["task_id"].as_u64();
+                        if policy.starts_with("stateful") {
+                            assert!(running.remove(&(job_id.unwrap(), task_id)));
+                        }
+                        *num_completions.entry(job_id.unwrap()).or_insert(0) += 1;
+                    }
+                    "job-departure" => {
+                        let (end, num_tasks) = arrivals[&job_id.unwrap()];
+                        assert!((time - end).abs() < 1e-6);
+                        assert_eq!(num_tasks, num_completions[&job_id.unwrap()]);
+                        num_departures += 1;
+                    }
+                    _ => {}
+                }
+            }
+            assert_eq!(out.num_jobs, arrivals.len() as u64);
+            assert!(num_departures > 0);
+            assert!(num_departures < arrivals.len());
+            for sample in &out.samples {
+                assert!(sample.active_tasks <= 199 * sample.active_jobs);
+            }
+            if policy.starts_with("stateful") {
+                assert!(!running.is_empty());
+                assert!(out.avg_busy_nodes > 0.0);
+                assert!(out.defragmentation_rounds > 0);
+            }
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_simulation_batch_stateless() -> anyhow::Result<()> {
+        // stateless policies account only for the running tasks, as the
+        // stateful ones, whose tasks are on the nodes until they finish
+        let mut out = vec![];
+        for policy in ["stateful-best-fit", "stateless-min-nodes"] {
+            out.push(
+                Simulation::new(Config {
+                    duration: 600.0,
+                    job_execution: JobExecution::Batch,
+                    policy: Policy::from(policy)?,
+                    sampling_interval: Some(10.0),
+                    ..test_config()
+                })?
+                .run(),
+            );
+        }
+        assert_eq!(out[0].samples.len(), out[1].samples.len());
+        for (stateful, stateless) in out[0].samples.iter().zip(out[1].samples.iter()) {
+            assert_eq!(stateful.active_tasks, stateless.active_tasks);
+            assert_eq!(stateful.cpu_used, stateless.cpu_used);
+        }
+        assert!(out[1].samples.iter().any(|x| x.cpu_used > 0));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_simulation_batch_cost_benefit() -> anyhow::Result<()> {
+        // some tasks of the jobs migrated are not started yet or have
+        // already finished
+        for seed in 0..3 {
+            let out = Simulation::new(Config {
+                duration: 600.0,
+                job_execution: JobExecution::Batch,
+                defragmentation_mode: DefragmentationMode::CostBenefit,
+                seed,
+                ..test_config()
+            })?
+            .run();
+            assert!(out.defragmentation_rounds > 0);
+            assert!(out.avg_busy_nodes > 0.0);
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_simulation_alibaba_trace() -> anyhow::Result<()> {
+        for job_execution in [JobExecution::Pipeline, JobExecution::Batch] {
+            let filename = std::env::temp_dir().join(format!(
+                "stateful-faas-sim-replay-{}-{}.jsonl",
+                job_execution,
+                std::process::id()
+            ));
+            let mut sim = Simulation::new(Config {
+                duration: 1000.0,
+                job_execution,
+                trace: Some(filename.to_str().unwrap().to_string()),
+                alibaba_trace: Some(sample_trace()?),
+                ..test_config()
+            })?;
+            let out = sim.run();
+            let content = std::fs::read_to_string(&filename)?;
+            std::fs::remove_file(&filename)?;
+
+            // the jobs arrive and depart as in the trace
+            let mut arrivals = vec![];
+            let mut departures = vec![];
+            for line in content.lines() {
+                let record = serde_json::from_str::<serde_json::Value>(line)?;
+                let time = record["time"].as_f64().unwrap();
+                match record["event"].as_str().unwrap() {
+                    "job-arrival" => arrivals.push(time),
+                    "job-departure" => departures.push(time),
+                    _ => {}
+                }
+            }
+            assert_eq!(5, out.num_jobs);
+            assert_eq!(vec![0.0, 12.0, 90.0, 190.0, 300.0], arrivals);
+            assert_eq!(vec![61.0, 142.0, 192.0, 215.0, 430.0], departures);
+            assert!(out.avg_busy_nodes > 0.0);
+        }
+
+        // the tasks of all the jobs must fit into a node
+        let node_type = NodeType::new("large", Resources::new(10000, 1048576), 50.0, 100.0, 1.0);
+        let jobs = crate::alibaba::read_batch_tasks(
+            "data/batch_task_sample.csv",
+            100.0,
+            100.0,
+            false,
+            &[node_type],
+        )?;
+        assert_eq!(6, jobs.len());
+        assert!(Simulation::new(Config {
+            alibaba_trace: Some(std::sync::Arc::new(jobs)),
+            ..test_config()
+        })
+        .is_err());
+        assert!(Simulation::new(Config {
+            alibaba_trace: Some(std::sync::Arc::new(vec![])),
+            ..test_config()
+        })
+        .is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_simulation_job_catalog() -> anyhow::Result<()> {
+        let filename = std::env::temp_dir().join(format!(
+            "stateful-faas-sim-catalog-{}.jsonl",
+            std::process::id()
+        ));
+        let config = |job_catalog: Option<&str>, alibaba_trace: bool| -> anyhow::Result<Config> {
+            Ok(Config {
+                duration: 600.0,
+                trace: Some(filename.to_str().unwrap().to_string()),
+                alibaba_trace: match alibaba_trace {
+                    true => Some(sample_trace()?),
+                    false => None,
+                },
+                job_catalog: job_catalog.map(|x| x.to_string()),
+                ..test_config()
+            })
+        };
+        assert!(Simulation::new(config(Some("data/catalog/catalog.csv"), true)?).is_err());
+
+        // the tasks of all the jobs must fit into a node
+        let directory =
+            std::env::temp_dir().join(format!("stateful-faas-sim-catalog-{}", std::process::id()));
+        std::fs::create_dir_all(&directory)?;
+        std::fs::write(directory.join("catalog.csv"), "large.dot,1.0\n")?;
+        std::fs::write(
+            directory.join("large.dot"),
+            "digraph {\n    0 [ label = \"(cpu = 5000, state = 1)\" ]\n}\n",
+        )?;
+        let res = Simulation::new(config(
+            Some(directory.join("catalog.csv").to_str().unwrap()),
+            false,
+        )?);
+        std::fs::remove_dir_all(&directory)?;
+        assert!(res.is_err());
+
+        let out = Simulation::new(config(Some("data/catalog/catalog.csv"), false)?)?.run();
+        let content = std::fs::read_to_string(&filename)?;
+        std::fs::remove_file(&filename)?;
+
+        // all the jobs are those in the catalog
+        let mut counts = std::collections::HashMap::new();
+        for line in content.lines() {
+            let record = serde_json::from_str::<serde_json::Value>(line)?;
+            if record["event"] == "job-arrival" {
+                let key = (
+                    record["num_tasks"].as_u64().unwrap(),
+                    record["total_cpu"].as_u64().unwrap(),
+                );
+                *counts.entry(key).or_insert(0_u64) += 1;
+            }
+        }
+        assert_eq!(2, counts.len());
+        assert_eq!(out.num_jobs, counts[&(4, 600)] + counts[&(3, 500)]);
+        assert!(counts[&(4, 600)] > counts[&(3, 500)]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_simulation_sampling() -> anyhow::Result<()> {
+        let mut sim = Simulation::new(Config {
+            defragmentation_interval: 300.0,
+            sampling_interval: Some(10.0),
+            ..test_config()
+        })?;
+        let out = sim.run();
+        assert!(out.samples.len() >= 359 && out.samples.len() <= 360);
+        for (i, sample) in out.samples.iter().enumerate() {
+            assert!((sample.time - 10.0 * (i + 1) as f64).abs() < 1e-6);
+            assert!(sample.active_tasks >= sample.active_jobs);
+            assert!(sample.busy_nodes <= sample.active_tasks);
+        }
+
+        // the average of the samples is close to the time average
+        let avg_busy_nodes = out.samples.iter().map(|x| x.busy_nodes).sum::<usize>() as f64
+            / out.samples.len() as f64;
+        println!("{} {}", avg_busy_nodes, out.avg_busy_nodes);
+        assert!((avg_busy_nodes - out.avg_busy_nodes).abs() < 0.1 * out.avg_busy_nodes);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_simulation_warm_up() -> anyhow::Result<()> {
+        let config = |policy: &str, warm_up, sampling_interval| -> anyhow::Result<Config> {
+            Ok(Config {
+                job_lifetime: 100.0,
+                defragmentation_mode: DefragmentationMode::Full,
+                policy: Policy::from(policy)?,
+                warm_up,
+                sampling_interval,
+                ..test_config()
+            })
+        };
+
+        // invalid configurations
+        assert!(
+            Simulation::new(config("stateful-best-fit", WarmUp::Fixed(3600.0), None)?).is_err()
+        );
+        assert!(Simulation::new(config("stateful-best-fit", WarmUp::Mser5, None)?).is_err());
+
+        // sampling alone does not affect the metrics, including the
+        // maximum busy nodes used with peak provisioning
+        for policy in ["stateful-best-fit", "stateless-max-balancing"] {
+            let out_no_sampling = Simulation::new(config(policy, WarmUp::None, None)?)?.run();
+            let out_sampling = Simulation::new(config(policy, WarmUp::None, Some(10.0))?)?.run();
+            // the additional events only change the rounding errors
+            let close = |x: f64, y: f64| (x - y).abs() <= 1e-9 * x.abs();
+            assert_eq!(0.0, out_sampling.warm_up);
+            assert!(close(
+                out_no_sampling.avg_busy_nodes,
+                out_sampling.avg_busy_nodes
+            ));
+            assert_eq!(out_no_sampling.migration_rate, out_sampling.migration_rate);
+            assert!(close(out_no_sampling.node_energy, out_sampling.node_energy));
+        }
+
+        // starting from an empty cluster lowers the average busy nodes
+        let out_none = Simulation::new(config("stateful-best-fit", WarmUp::None, None)?)?.run();
+        let out_fixed =
+            Simulation::new(config("stateful-best-fit", WarmUp::Fixed(600.0), None)?)?.run();
+        let out_mser5 =
+            Simulation::new(config("stateful-best-fit", WarmUp::Mser5, Some(10.0))?)?.run();
+        println!("{:?}\n{:?}\n{:?}", out_none, out_fixed, out_mser5);
+        assert_eq!(600.0, out_fixed.warm_up);
+        assert!(out_fixed.avg_busy_nodes > out_none.avg_busy_nodes);
+        assert!(out_fixed.defragmentation_rounds < out_none.defragmentation_rounds);
+        assert!(out_mser5.warm_up > 0.0 && out_mser5.warm_up <= 1800.0);
+        assert_eq!(359, out_mser5.samples.len());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_simulation_output_metrics() -> anyhow::Result<()> {
+        let out = Simulation::new(Config {
+            duration: 60.0,
+            defragmentation_interval: 300.0,
+            ..test_config()
+        })?
+        .run();
+
+        // every metric is a column with the same value as in the CSV row
+        let header = Output::header().split(',').collect::<Vec<&str>>();
+        let row = out.to_string();
+        let row = row.split(',').collect::<Vec<&str>>();
+        for name in Output::metrics() {
+            let column = header.iter().position(|x| x == name).unwrap();
+            assert_eq!(row[column].parse::<f64>()?, out.metric(name).unwrap());
+        }
+        assert!(out.metric("seed").is_none());
+        assert!(out.metric("repacking-order").is_none());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_simulation_run_batch() -> anyhow::Result<()> {
+        let config = |duration, seed| -> anyhow::Result<Config> {
+            Ok(Config {
+                duration,
+                defragmentation_mode: DefragmentationMode::Full,
+                policy: Policy::from("stateful-random")?,
+                seed,
+                ..test_config()
+            })
+        };
+
+        // the outputs are in the same order as the configurations
+        // and the same as if the simulations were run sequentially
+        let seeds = [3, 1, 4, 0, 2];
+        for concurrency in [1, 2, 10] {
+            let outputs = run_batch(
+                seeds
+                    .iter()
+                    .map(|seed| config(600.0, *seed))
+                    .collect::<anyhow::Result<Vec<Config>>>()?,
+                concurrency,
+            )?;
+            assert_eq!(
+                seeds.to_vec(),
+                outputs.iter().map(|x| x.seed).collect::<Vec<u64>>()
+            );
+            for (seed, output) in seeds.iter().zip(outputs.iter()) {
+                let expected = Simulation::new(config(600.0, *seed)?)?.run();
+                assert_eq!(expected.avg_busy_nodes, output.avg_busy_nodes);
+                assert_eq!(expected.total_traffic, output.total_traffic);
+            }
+        }
+
+        // errors are returned
+        assert!(run_batch(vec![config(600.0, 0)?, config(0.0, 1)?], 2).is_err());
+        assert!(run_batch(vec![config(600.0, 0)?], 0).is_err());
+        assert!(run_batch(vec![], 2)?.is_empty());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_simulation_arrival_profile() -> anyhow::Result<()> {
+        let mut sim = Simulation::new(Config {
+            duration: 7200.0,
+            job_interarrival: 0.5,
+            arrival_profile: ArrivalProfile::Diurnal {
+                amplitude: 0.9,
+                period: 3600.0,
+            },
+            defragmentation_interval: 300.0,
+            policy: Policy::from("stateless-min-nodes")?,
+            sampling_interval: Some(900.0),
+            ..test_config()
+        })?;
+        let out = sim.run();
+
+        // the average rate is the same as without a profile
+        assert!((out.num_jobs as f64 - 14400.0).abs() < 0.05 * 14400.0);
+
+        // many more jobs are active at the peak than at the trough
+        let active_jobs = out
+            .samples
+            .iter()
+            .map(|x| x.active_jobs)
+            .collect::<Vec<usize>>();
+        println!("{:?}", active_jobs);
+        assert!(active_jobs[1] > 5 * active_jobs[3]);
+        assert!(active_jobs[5] > 5 * active_jobs[3]);
+
+        Ok(())
+    }
 }
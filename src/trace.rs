@@ -0,0 +1,163 @@
+/// Record of an event in the trace of a simulation, with times in s.
+#[derive(Debug, Clone, PartialEq, serde::Serialize)]
+#[serde(tag = "event", rename_all = "kebab-case")]
+pub enum Record {
+    /// A new job arrives, with a summary of its DAG.
+    JobArrival {
+        time: f64,
+        job_id: u64,
+        lifetime: f64,
+        num_tasks: usize,
+        num_edges: usize,
+        total_cpu: usize,
+        total_state_size: usize,
+        total_arg_size: usize,
+    },
+    /// A task is placed on a node, when its job arrives or, with batch
+    /// execution, when the task starts.
+    TaskPlacement {
+        time: f64,
+        job_id: u64,
+        task_id: u32,
+        node_id: usize,
+    },
+    /// A task finishes, with batch execution.
+    TaskCompletion {
+        time: f64,
+        job_id: u64,
+        task_id: u32,
+    },
+    /// An active job ends.
+    JobDeparture { time: f64, job_id: u64 },
+    /// A defragmentation round is carried out.
+    Defragmentation {
+        time: f64,
+        fragmentation_index: f64,
+        busy_nodes_before: usize,
+        busy_nodes_after: usize,
+        num_migrations: usize,
+        migration_traffic: usize,
+    },
+    /// A task is migrated during a defragmentation round, with the
+    /// duration of the transfer of its state, in s, if not instantaneous.
+    Migration {
+        time: f64,
+        job_id: u64,
+        task_id: u32,
+        src_node_id: usize,
+        dst_node_id: usize,
+        state_size: usize,
+        duration: Option<f64>,
+    },
+}
+
+/// Writer of the trace of a simulation in JSON Lines format,
+/// i.e., one JSON object per line, which does nothing if disabled.
+pub struct Trace {
+    writer: Option<Box<dyn std::io::Write + Send>>,
+}
+
+impl Trace {
+    /// Create a trace that does not record anything.
+    pub fn disabled() -> Self {
+        Self { writer: None }
+    }
+
+    /// Create a trace written to the given file, which is truncated.
+    pub fn from_file(filename: &str) -> anyhow::Result<Self> {
+        let file = std::fs::File::create(filename)
+            .map_err(|err| anyhow::anyhow!("could not create trace file {}: {}", filename, err))?;
+        Ok(Self::from_writer(Box::new(std::io::BufWriter::new(file))))
+    }
+
+    /// Create a trace written to the given writer.
+    pub fn from_writer(writer: Box<dyn std::io::Write + Send>) -> Self {
+        Self {
+            writer: Some(writer),
+        }
+    }
+
+    /// Return true if the records are actually written.
+    pub fn enabled(&self) -> bool {
+        self.writer.is_some()
+    }
+
+    /// Write a record, disabling the trace upon failure.
+    pub fn record(&mut self, record: &Record) {
+        if let Some(writer) = &mut self.writer {
+            let res = serde_json::to_writer(&mut *writer, record)
+                .map_err(anyhow::Error::from)
+                .and_then(|_| writeln!(writer).map_err(anyhow::Error::from));
+            if let Err(err) = res {
+                log::error!("error when writing the trace, disabling it: {}", err);
+                self.writer = None;
+            }
+        }
+    }
+
+    /// Flush the records written so far.
+    pub fn flush(&mut self) {
+        if let Some(writer) = &mut self.writer {
+            if let Err(err) = writer.flush() {
+                log::error!("error when flushing the trace: {}", err);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Writer into a buffer shared with the test.
+    #[derive(Clone, Default)]
+    struct SharedBuffer(std::sync::Arc<std::sync::Mutex<Vec<u8>>>);
+
+    impl std::io::Write for SharedBuffer {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_trace_json_lines() {
+        let buffer = SharedBuffer::default();
+        let mut trace = Trace::from_writer(Box::new(buffer.clone()));
+        assert!(trace.enabled());
+        trace.record(&Record::JobDeparture {
+            time: 1.5,
+            job_id: 42,
+        });
+        trace.record(&Record::Migration {
+            time: 2.0,
+            job_id: 42,
+            task_id: 1,
+            src_node_id: 3,
+            dst_node_id: 0,
+            state_size: 100,
+            duration: None,
+        });
+        trace.flush();
+
+        let content = String::from_utf8(buffer.0.lock().unwrap().clone()).unwrap();
+        assert_eq!(
+            vec![
+                r#"{"event":"job-departure","time":1.5,"job_id":42}"#,
+                r#"{"event":"migration","time":2.0,"job_id":42,"task_id":1,"src_node_id":3,"dst_node_id":0,"state_size":100,"duration":null}"#,
+            ],
+            content.lines().collect::<Vec<&str>>()
+        );
+
+        let mut trace = Trace::disabled();
+        assert!(!trace.enabled());
+        trace.record(&Record::JobDeparture {
+            time: 1.5,
+            job_id: 42,
+        });
+    }
+}
@@ -0,0 +1,107 @@
+/// How the warm-up period at the beginning of a simulation, which is
+/// excluded from the metrics, is determined.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum WarmUp {
+    /// There is no warm-up period.
+    None,
+    /// The warm-up period has a fixed duration, in s.
+    Fixed(f64),
+    /// The warm-up period is detected with the MSER-5 rule applied to
+    /// the series of busy nodes sampled during the simulation.
+    Mser5,
+}
+
+impl std::str::FromStr for WarmUp {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> anyhow::Result<Self> {
+        match s {
+            "none" => Ok(Self::None),
+            "mser-5" => Ok(Self::Mser5),
+            _ => match s.parse::<f64>() {
+                Ok(duration) => Ok(Self::Fixed(duration)),
+                Err(_) => Err(anyhow::anyhow!("unknown warm-up: {}", s)),
+            },
+        }
+    }
+}
+
+impl std::fmt::Display for WarmUp {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::None => write!(f, "none"),
+            Self::Fixed(duration) => write!(f, "{}", duration),
+            Self::Mser5 => write!(f, "mser-5"),
+        }
+    }
+}
+
+/// Return the number of initial observations of a series to be discarded
+/// according to the Marginal Standard Error Rule (MSER) applied to the
+/// means of batches of 5 observations.
+///
+/// The truncation point minimizes the variance of the mean of the
+/// remaining batches divided by their number, searched only in the first
+/// half of the series because the statistic is unreliable near its end.
+pub fn mser5(series: &[f64]) -> usize {
+    let batches = series
+        .chunks_exact(5)
+        .map(|x| x.iter().sum::<f64>() / 5.0)
+        .collect::<Vec<f64>>();
+    let num_batches = batches.len();
+    if num_batches < 2 {
+        return 0;
+    }
+
+    let mut best = (f64::MAX, 0);
+    for d in 0..=num_batches / 2 {
+        let remaining = &batches[d..];
+        let n = remaining.len() as f64;
+        let mean = remaining.iter().sum::<f64>() / n;
+        let mser = remaining.iter().map(|x| (x - mean).powi(2)).sum::<f64>() / (n * n);
+        if mser < best.0 {
+            best = (mser, d);
+        }
+    }
+    5 * best.1
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_warm_up_from_str() {
+        assert_eq!(WarmUp::None, "none".parse::<WarmUp>().unwrap());
+        assert_eq!(WarmUp::Mser5, "mser-5".parse::<WarmUp>().unwrap());
+        assert_eq!(WarmUp::Fixed(120.0), "120".parse::<WarmUp>().unwrap());
+        assert!("mser".parse::<WarmUp>().is_err());
+        for warm_up in [WarmUp::None, WarmUp::Fixed(1.5), WarmUp::Mser5] {
+            assert_eq!(warm_up, warm_up.to_string().parse::<WarmUp>().unwrap());
+        }
+    }
+
+    #[test]
+    fn test_warm_up_mser5() {
+        // too short
+        assert_eq!(0, mser5(&[]));
+        assert_eq!(0, mser5(&[1.0, 2.0, 3.0, 4.0, 5.0, 6.0]));
+
+        // stationary
+        let series = (0..100)
+            .map(|x| 10.0 + (x % 3) as f64)
+            .collect::<Vec<f64>>();
+        assert!(mser5(&series) <= 10);
+
+        // linear ramp up to a stationary level
+        let series = (0..200)
+            .map(|x| match x < 50 {
+                true => x as f64,
+                false => 50.0 + (x % 3) as f64,
+            })
+            .collect::<Vec<f64>>();
+        let truncation = mser5(&series);
+        assert_eq!(0, truncation % 5);
+        assert!((45..=60).contains(&truncation), "{}", truncation);
+    }
+}
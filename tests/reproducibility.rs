@@ -0,0 +1,53 @@
+/// Run the simulator in a separate process and return the lines of the
+/// output file, without the execution time, which is the last column.
+fn run_simulator(policy: &str, output: &str) -> Vec<String> {
+    let output = std::env::temp_dir().join(format!(
+        "stateful-faas-sim-reproducibility-{}-{}.csv",
+        std::process::id(),
+        output
+    ));
+    let status = std::process::Command::new(env!("CARGO_BIN_EXE_stateful_faas_sim"))
+        .current_dir(env!("CARGO_MANIFEST_DIR"))
+        .args([
+            "--policy",
+            policy,
+            "--duration",
+            "600",
+            "--defragmentation-interval",
+            "60",
+            "--seed-init",
+            "0",
+            "--seed-end",
+            "3",
+            "--output",
+        ])
+        .arg(&output)
+        .status()
+        .expect("could not run the simulator");
+    assert!(status.success());
+    let content = std::fs::read_to_string(&output).expect("could not read the output file");
+    std::fs::remove_file(&output).expect("could not remove the output file");
+
+    content
+        .lines()
+        .map(|line| match line.rfind(',') {
+            Some(pos) => line[..pos].to_string(),
+            None => line.to_string(),
+        })
+        .collect::<Vec<String>>()
+}
+
+#[test]
+fn test_reproducibility_separate_processes() {
+    for policy in [
+        "stateful-best-fit",
+        "stateful-random",
+        "stateful-graph-partitioning",
+    ] {
+        let first = run_simulator(policy, "first");
+        let second = run_simulator(policy, "second");
+        assert_eq!(4, first.len());
+        assert!(first[1].starts_with("0,") && first[3].starts_with("2,"));
+        assert_eq!(first, second);
+    }
+}